@@ -19,31 +19,51 @@ pub const FWTO_WS: &str = "0.fwto";
 #[derive(Debug, Clone, serde::Deserialize)]
 pub struct Json {
     pub project         : Project,
+    pub layers          : Option<Vec<Layer>>,
     pub ibvovrd         : Option<StdOvrd>,
     pub oemovrd         : Option<StdOvrd>,
     pub aptio_v         : Option<AptioV>,
+    pub difftool        : Option<Tool>,
+    pub mergetool       : Option<Tool>,
+    pub renames         : Option<Renames>,
+    pub build           : Option<BuildConfig>,
 }
 
 impl Json {
-    pub fn get(conf: &Option<String>) -> Option<Json> {
-        let mut audk_json: Option<Json> = None;
-
+    /// Dir of audk configurations, the sub-folder (.fwto) where fwto is
+    pub fn home() -> std::path::PathBuf {
         let pcfg = std::env::current_exe().unwrap().parent().unwrap().join(".fwto");
         if !pcfg.is_dir() {
             fs::create_dir_all(&pcfg).unwrap();
         }
-        let fsel = pcfg.join(".audk.default");
-        let conf_from_default = if fsel.is_file() {
+        pcfg
+    }
+
+    /// Name of current audk configuration, given or selected before
+    pub fn name(conf: &Option<String>) -> String {
+        if let Some(conf) = conf {
+            return conf.clone();
+        }
+        let fsel = Self::home().join(".audk.default");
+        if fsel.is_file() {
             String::from_utf8(fs::read(&fsel).unwrap()).unwrap()
         } else {
             String::from("default.json")
-        };
+        }
+    }
 
-        let conf = if let Some(conf) = conf {
-            conf
-        } else {
-            &conf_from_default
-        };
+    /// Select conf as audk default configuration
+    pub fn select(conf: &str) {
+        let fsel = Self::home().join(".audk.default");
+        let mut fsel = fs::OpenOptions::new().create(true).write(true).truncate(true).open(&fsel).unwrap();
+        fsel.write_all(conf.as_bytes()).unwrap();
+    }
+
+    pub fn get(conf: &Option<String>) -> Option<Json> {
+        let mut audk_json: Option<Json> = None;
+
+        let pcfg = Self::home();
+        let conf = &Self::name(conf);
         println!("---------------------------");
         println!("INF: current configuration: {:?}", conf);
         println!("---------------------------");
@@ -54,13 +74,27 @@ impl Json {
                 fs::OpenOptions::new().read(true).open(fcfg).unwrap()
             ).expect(&format!("ERR: invalid format of {}", conf)));
             // once success, update default audk default configuration to .audk.default
-            let mut fsel = fs::OpenOptions::new().create(true).write(true).truncate(true).open(&fsel).unwrap();
-            fsel.write(conf.as_bytes()).unwrap();
+            Self::select(conf);
         } else {
             println!("WRN: no audk configuration: {:?}", conf);
         }
         audk_json
     }
+
+    /// Override layers in order, fallback to ibvovrd + oemovrd if layers is not given
+    pub fn layers(&self) -> Vec<Layer> {
+        if let Some(layers) = &self.layers {
+            return layers.clone();
+        }
+        let mut layers = Vec::new();
+        if let Some(ibvovrd) = &self.ibvovrd {
+            layers.push(Layer::from("ibvovrd", ibvovrd));
+        }
+        if let Some(oemovrd) = &self.oemovrd {
+            layers.push(Layer::from("oemovrd", oemovrd));
+        }
+        layers
+    }
 }
 
 #[derive(Debug, Clone, StructOpt, serde::Deserialize)]
@@ -85,6 +119,156 @@ pub struct StdOvrd {
     pub org             : Option<std::path::PathBuf>,
 }
 
+/// One override layer, where overrides are in and which cif maps them
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Layer {
+    pub name            : String,
+    pub cif             : Option<std::path::PathBuf>,
+    pub dst             : Option<std::path::PathBuf>,
+    pub org             : Option<std::path::PathBuf>,
+}
+
+impl Layer {
+    pub fn from(name: &str, ovrd: &StdOvrd) -> Self {
+        Self {
+            name: String::from(name), cif: ovrd.cif.clone(), dst: ovrd.dst.clone(), org: ovrd.org.clone()
+        }
+    }
+}
+
+/// Override layers from the lowest to the highest, overrides of project are in the highest,
+/// which is writable only if it is oemovrd or the last one of layers in json
+#[derive(Debug, Clone)]
+pub struct Layers(pub Vec<Layer>, bool);
+
+impl Layers {
+    /// Layers of json, with the writable one overridden by cmdline, or oemovrd of cmdline on top of them
+    pub fn new(cfg_layers: Vec<Layer>, cfg_explicit: bool, opt_oemovrd: &StdOvrd) -> Self {
+        let mut layers = cfg_layers;
+        let mut writable = (cfg_explicit && !layers.is_empty()) || layers.last().map(|top| top.name == "oemovrd").unwrap_or(false);
+        if writable {
+            let top = layers.last_mut().unwrap();
+            if opt_oemovrd.cif.is_some() { top.cif = opt_oemovrd.cif.clone(); }
+            if opt_oemovrd.dst.is_some() { top.dst = opt_oemovrd.dst.clone(); }
+            if opt_oemovrd.org.is_some() { top.org = opt_oemovrd.org.clone(); }
+        } else if opt_oemovrd.cif.is_some() || opt_oemovrd.dst.is_some() || opt_oemovrd.org.is_some() {
+            // ibvovrd is never written, even if it is the only one in json
+            layers.push(Layer::from("oemovrd", opt_oemovrd));
+            writable = true;
+        }
+        Self(layers, writable)
+    }
+
+    pub fn top(&self) -> Option<&Layer> {
+        if self.1 { self.0.last() } else { None }
+    }
+
+    /// Layers below the highest one, from the lowest
+    pub fn lower(&self) -> &[Layer] {
+        match self.0.len() {
+            n if n > 0 && self.1 => &self.0[..n - 1],
+            _ => &self.0,
+        }
+    }
+
+    /// Dst of layers below the highest one, from the lowest
+    pub fn lower_dsts(&self) -> Vec<&std::path::PathBuf> {
+        self.lower().iter().filter_map(|layer| layer.dst.as_ref()).collect()
+    }
+}
+
+/// External compare or merge tool, {base}, {local}, {remote} and {merged} in args are replaced by files
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Tool {
+    pub cmd             : std::path::PathBuf,
+    pub args            : Vec<String>,
+    /// Exit code 0 means resolved, or else the merged file must be changed, default true
+    pub trust_exit_code : Option<bool>,
+}
+
+/// Rename policy of cbup, overridden by cmdline
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Renames {
+    /// Similarity threshold of renames, default 75%
+    pub find_renames    : Option<String>,
+    /// Similarity threshold of copies, copy detection is off if it is None
+    pub find_copies     : Option<String>,
+    /// Rename map to force or deny pairings
+    pub rename_map      : Option<std::path::PathBuf>,
+}
+
+/// Options of build out of aptio_v
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct BuildConfig {
+    pub artifacts       : Option<Artifacts>,
+    /// Named sets of make goals and variables, e.g. debug, release and quick
+    pub presets         : Option<std::collections::BTreeMap<String, Preset>>,
+    /// Variants to build by --matrix
+    pub matrix          : Option<Vec<Variant>>,
+    /// Dirs to discover ewdk, tools and pycmd in, before common locations
+    pub toolkit_roots   : Option<Vec<std::path::PathBuf>>,
+    /// Versions or hashes of ewdk, tools and pycmd that the project wants
+    pub pins            : Option<Pins>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Pins {
+    pub ewdk            : Option<Pin>,
+    pub tools           : Option<Pin>,
+    pub pycmd           : Option<Pin>,
+}
+
+/// Toolkit wanted, by its version and SHA-256 of its key files
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Pin {
+    /// Version as it is, or a prefix of it by dots, e.g. 3.8 for 3.8.10
+    pub version         : Option<String>,
+    /// SHA-256 of files, relative to the dir of ewdk and tools, or of pycmd
+    pub sha256          : Option<std::collections::BTreeMap<std::path::PathBuf, String>>,
+}
+
+/// One board variant, as a veb built with a preset
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Variant {
+    /// Name of logs and artifacts dirs, default <veb>-<preset>
+    pub name            : Option<String>,
+    pub veb             : Option<std::path::PathBuf>,
+    pub preset          : Option<String>,
+}
+
+impl Variant {
+    pub fn name(&self) -> String {
+        if let Some(name) = self.name.as_ref() {
+            return name.clone();
+        }
+        let veb = self.veb.as_ref().and_then(|veb| veb.file_stem()).map(|stem| stem.to_string_lossy().to_string()).unwrap_or_else(|| String::from("default"));
+        match self.preset.as_ref() {
+            Some(preset) => veb + "-" + preset,
+            None => veb,
+        }
+    }
+}
+
+/// Make goals and variables of a build preset
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Preset {
+    /// Goals of make, default rebuild
+    pub targets         : Option<Vec<String>>,
+    /// Variables of make as TOKEN and VALUE
+    pub defines         : Option<std::collections::BTreeMap<String, String>>,
+}
+
+/// Files to collect after a successful build
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Artifacts {
+    /// Glob patterns relative to workspace, e.g. Build/**/*.rom
+    pub patterns        : Vec<String>,
+    /// Naming template with {veb}, {branch}, {shortsha}, {date} and {name}, default {veb}_{branch}_{shortsha}_{date}
+    pub name            : Option<String>,
+    /// Dir where artifacts are copied to, default 0.fwto/build/artifacts
+    pub output          : Option<std::path::PathBuf>,
+}
+
 #[derive(Debug, Clone, StructOpt, serde::Deserialize)]
 pub struct AptioV {
     #[structopt(flatten)]
@@ -92,7 +276,7 @@ pub struct AptioV {
     #[structopt(flatten)]
     pub toolkit         : AptioToolkit,
     #[structopt(skip)]
-    pub scripts         : Option<Scripts>,
+    pub scripts         : Option<Box<Scripts>>,
 }
 
 #[derive(Debug, Clone, StructOpt, serde::Deserialize)]
@@ -120,12 +304,95 @@ pub struct Scripts {
     pub work_space      : Option<Vec<ScriptsDesc>>,
     pub fore_build      : Option<Vec<ScriptsDesc>>,
     pub post_build      : Option<Vec<ScriptsDesc>>,
+    pub pre_ovrd        : Option<Vec<ScriptsDesc>>,
+    pub post_ovrd       : Option<Vec<ScriptsDesc>>,
+    pub pre_cbup        : Option<Vec<ScriptsDesc>>,
+    pub post_cbup       : Option<Vec<ScriptsDesc>>,
+    pub post_view       : Option<Vec<ScriptsDesc>>,
+}
+
+impl Scripts {
+    /// Hooks of the stage, which is named as it is in json
+    pub fn hooks(&self, stage: &str) -> &Option<Vec<ScriptsDesc>> {
+        match stage {
+            "work_space" => &self.work_space,
+            "fore_build" => &self.fore_build,
+            "post_build" => &self.post_build,
+            "pre_ovrd"   => &self.pre_ovrd,
+            "post_ovrd"  => &self.post_ovrd,
+            "pre_cbup"   => &self.pre_cbup,
+            "post_cbup"  => &self.post_cbup,
+            "post_view"  => &self.post_view,
+            _            => &None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, serde::Deserialize)]
 pub struct ScriptsDesc {
-    pub interpreter     : std::path::PathBuf,
+    /// Name or path of interpreter, which is looked up on PATH by name, default the shell to run inline command
+    pub interpreter     : Option<std::path::PathBuf>,
     pub args            : Option<Vec<String>>,
     pub farg            : Option<String>,
-    pub file            : std::path::PathBuf,
+    /// Script file, relative to workspace or absolute
+    pub file            : Option<std::path::PathBuf>,
+    /// Inline command, instead of script file
+    pub run             : Option<String>,
+    /// What to do if the hook fails, times out or is missing, default warn
+    pub on_failure      : Option<OnFailure>,
+    /// Seconds to wait before the hook is killed, no limit if it is None
+    pub timeout         : Option<u64>,
+    /// Dir where the hook runs, default workspace
+    pub cwd             : Option<std::path::PathBuf>,
+    /// Extra environment variables of the hook
+    pub env             : Option<std::collections::BTreeMap<String, String>>,
+    /// Variant on windows, whose fields win over the common ones
+    pub windows         : Option<Box<ScriptsDesc>>,
+    /// Variant on linux, whose fields win over the common ones
+    pub linux           : Option<Box<ScriptsDesc>>,
+}
+
+impl ScriptsDesc {
+    /// The hook on current OS, with fields of its variant merged
+    pub fn for_os(&self) -> Self {
+        let variant = if cfg!(target_os = "windows") { &self.windows } else { &self.linux };
+        let variant = match variant {
+            Some(variant) => variant,
+            None => return Self { windows: None, linux: None, ..self.clone() },
+        };
+        // file and run are exclusive, so variant of either wins over both
+        let (file, run) = if variant.file.is_some() || variant.run.is_some() {
+            (variant.file.clone(), variant.run.clone())
+        } else {
+            (self.file.clone(), self.run.clone())
+        };
+        let env = match (&self.env, &variant.env) {
+            (Some(env), Some(more)) => Some(env.iter().chain(more.iter()).map(|(k, v)| (k.clone(), v.clone())).collect()),
+            (env, more) => more.clone().or_else(|| env.clone()),
+        };
+        Self {
+            interpreter : variant.interpreter.clone().or_else(|| self.interpreter.clone()),
+            args        : variant.args.clone().or_else(|| self.args.clone()),
+            farg        : variant.farg.clone().or_else(|| self.farg.clone()),
+            file,
+            run,
+            on_failure  : variant.on_failure.or(self.on_failure),
+            timeout     : variant.timeout.or(self.timeout),
+            cwd         : variant.cwd.clone().or_else(|| self.cwd.clone()),
+            env,
+            windows     : None,
+            linux       : None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnFailure {
+    /// Stop the rest hooks and the operation
+    Abort,
+    /// Report it and go on
+    Warn,
+    /// Go on silently
+    Ignore,
 }
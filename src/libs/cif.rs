@@ -0,0 +1,42 @@
+/*++ @file
+
+  Copyright ©2021 Liu Yi, liuyi28@lenovo.com
+
+  This program is just made available under the terms and conditions of the
+  MIT license: http://www.efikarl.com/mit-license.html
+
+  THE PROGRAM IS DISTRIBUTED UNDER THE MIT LICENSE ON AN "AS IS" BASIS,
+  WITHOUT WARRANTIES OR REPRESENTATIONS OF ANY KIND, EITHER EXPRESS OR IMPLIED.
+--*/
+
+use std::fs;
+use path_slash::PathBufExt;
+
+/// One `"<dst>";"<src>"` override statement of a cif
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// Override file, relative to the dir where cif is in
+    pub dst             : std::path::PathBuf,
+    /// Overridden file, relative to workspace
+    pub src             : std::path::PathBuf,
+}
+
+pub fn parse_line(line: &str) -> Option<Entry> {
+    let (fdst, fsrc) = line.trim().split_once(";")?;
+    let (fdst, fsrc) = (fdst.trim(), fsrc.trim());
+    if fdst.len() < 2 || !fdst.starts_with('"') || !fdst.ends_with('"') ||
+       fsrc.len() < 2 || !fsrc.starts_with('"') || !fsrc.ends_with('"') {
+        return None;
+    }
+    Some(Entry {
+        dst: std::path::PathBuf::from_slash(fdst.trim_matches('"').replace('\\', "/")),
+        src: std::path::PathBuf::from_slash(fsrc.trim_matches('"').replace('\\', "/")),
+    })
+}
+
+pub fn entries(cif: &std::path::Path) -> Vec<Entry> {
+    match fs::read(cif) {
+        Ok(buf) => String::from_utf8_lossy(&buf).lines().filter_map(parse_line).collect(),
+        Err(_)  => Vec::new(),
+    }
+}
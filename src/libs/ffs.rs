@@ -11,7 +11,7 @@
 
 use std::fs;
 
-fn force_rw<P: AsRef<std::path::Path>>(path: P) -> std::result::Result<(), std::io::Error> {
+pub fn force_rw<P: AsRef<std::path::Path>>(path: P) -> std::result::Result<(), std::io::Error> {
   if path.as_ref().is_file() {
     let mut perms = fs::metadata(&path)?.permissions();
     if perms.readonly() {
@@ -33,3 +33,145 @@ pub fn copy<P: AsRef<std::path::Path>, Q: AsRef<std::path::Path>>(from: P, to: Q
 
   fs::copy(&from, &to)
 }
+
+pub fn walk<P: AsRef<std::path::Path>, F: FnMut(&std::path::Path, bool) -> bool>(path: P, visit: &mut F) {
+  // visit(path, is_dir), and only dir visited with true will be walked into
+  let mut entries = match fs::read_dir(&path) {
+    Ok(entries) => entries.flatten().map(|e| e.path()).collect::<Vec<_>>(),
+    Err(_) => return,
+  };
+  entries.sort();
+  for entry in entries {
+    if entry.is_dir() {
+      if visit(&entry, true) {
+        walk(&entry, visit);
+      }
+    } else {
+      visit(&entry, false);
+    }
+  }
+}
+
+pub fn is_readonly<P: AsRef<std::path::Path>>(path: P) -> bool {
+  match fs::metadata(&path) {
+    Ok(metadata) => metadata.permissions().readonly(),
+    Err(_) => false,
+  }
+}
+
+pub fn normalize<P: AsRef<std::path::Path>>(path: P) -> std::path::PathBuf {
+  // resolve "." and ".." lexically, as files may not be there
+  let mut normalized = std::path::PathBuf::new();
+  for component in path.as_ref().components() {
+    match component {
+      std::path::Component::CurDir => {},
+      std::path::Component::ParentDir => { normalized.pop(); },
+      component => normalized.push(component),
+    }
+  }
+  normalized
+}
+
+fn glob_component(pattern: &[char], name: &[char]) -> bool {
+  // '*' matches any chars, and '?' matches one char, case-insensitive on windows
+  match (pattern.first(), name.first()) {
+    (None, None) => true,
+    (Some('*'), _) => glob_component(&pattern[1..], name) || (!name.is_empty() && glob_component(pattern, &name[1..])),
+    (Some('?'), Some(_)) => glob_component(&pattern[1..], &name[1..]),
+    (Some(p), Some(n)) => {
+      let same = if cfg!(target_os = "windows") { p.eq_ignore_ascii_case(n) } else { p == n };
+      same && glob_component(&pattern[1..], &name[1..])
+    },
+    _ => false,
+  }
+}
+
+fn glob_match(pattern: &[Vec<char>], path: &[Vec<char>]) -> bool {
+  // '**' matches zero or more dirs
+  match pattern.first() {
+    None => path.is_empty(),
+    Some(p) if p.iter().collect::<String>() == "**" => glob_match(&pattern[1..], path) || (!path.is_empty() && glob_match(pattern, &path[1..])),
+    Some(p) => !path.is_empty() && glob_component(p, &path[0]) && glob_match(&pattern[1..], &path[1..]),
+  }
+}
+
+pub fn glob(pattern: &str) -> Vec<std::path::PathBuf> {
+  // walk from the dir before the 1st wildcard, and files matched are in order
+  let pattern = pattern.replace('\\', "/");
+  // prefix and root dir are kept together, e.g. C:/ rather than C: relative to current dir of drive C
+  let mut root = std::path::PathBuf::new();
+  let mut parts = Vec::new();
+  for component in std::path::Path::new(&pattern).components() {
+    match component {
+      std::path::Component::Prefix(_) | std::path::Component::RootDir => root.push(component.as_os_str()),
+      std::path::Component::CurDir => {},
+      _ => parts.push(component.as_os_str().to_string_lossy().to_string()),
+    }
+  }
+  let fixed = parts.iter().take_while(|p| !p.contains('*') && !p.contains('?')).count();
+  for part in &parts[..fixed] {
+    root.push(part);
+  }
+  if fixed == parts.len() {
+    return if root.is_file() { vec![root] } else { Vec::new() };
+  }
+  let rest = parts[fixed..].iter().map(|p| p.chars().collect::<Vec<_>>()).collect::<Vec<_>>();
+  let mut matched = Vec::new();
+  let walk_root = if root.as_os_str().is_empty() { std::path::PathBuf::from(".") } else { root.clone() };
+  walk(&walk_root, &mut |path, is_dir| {
+    if !is_dir {
+      let relative = path.strip_prefix(&walk_root).unwrap();
+      let relative = relative.components().map(|c| c.as_os_str().to_string_lossy().chars().collect::<Vec<_>>()).collect::<Vec<_>>();
+      if glob_match(&rest, &relative) {
+        matched.push(root.join(path.strip_prefix(&walk_root).unwrap()));
+      }
+    }
+    is_dir
+  });
+  matched
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn matches(pattern: &str, path: &str) -> bool {
+    let chars = |s: &str| s.split('/').map(|p| p.chars().collect::<Vec<_>>()).collect::<Vec<_>>();
+    glob_match(&chars(pattern), &chars(path))
+  }
+
+  #[test]
+  fn wildcards() {
+    assert!(matches("*.efi", "Shell.efi"));
+    assert!(!matches("*.efi", "X64/Shell.efi"));
+    assert!(matches("Shell?.efi", "Shell2.efi"));
+    assert!(!matches("Shell?.efi", "Shell.efi"));
+    assert!(!matches("Shell?.efi", "Shell10.efi"));
+  }
+
+  #[test]
+  fn double_star() {
+    assert!(matches("**/*.efi", "Shell.efi"));
+    assert!(matches("**/*.efi", "DEBUG/X64/Shell.efi"));
+    assert!(matches("DEBUG/**/X64/*.efi", "DEBUG/X64/Shell.efi"));
+    assert!(matches("DEBUG/**/X64/*.efi", "DEBUG/a/b/X64/Shell.efi"));
+    assert!(!matches("DEBUG/**/X64/*.efi", "RELEASE/X64/Shell.efi"));
+    assert!(matches("**", "a/b/c"));
+  }
+
+  #[test]
+  fn absolute_patterns() {
+    let root = std::env::temp_dir().join(format!("fwto-glob-{}", std::process::id()));
+    fs::create_dir_all(root.join("Build/X64")).unwrap();
+    fs::write(root.join("Build/a.rom"), b"").unwrap();
+    fs::write(root.join("Build/X64/b.rom"), b"").unwrap();
+    fs::write(root.join("Build/X64/c.map"), b"").unwrap();
+    let base = root.to_string_lossy().replace('\\', "/");
+    let found = glob(&(base.clone() + "/Build/**/*.rom"));
+    assert_eq!(found, vec![root.join("Build").join("X64/b.rom"), root.join("Build").join("a.rom")]);
+    assert!(found.iter().all(|path| path.is_absolute()));
+    assert_eq!(glob(&(base.clone() + "/Build/X64/c.map")), vec![root.join("Build/X64/c.map")]);
+    assert_eq!(glob(&(base.replace('/', "\\") + "\\Build\\?.rom")), vec![root.join("Build").join("a.rom")]);
+    fs::remove_dir_all(&root).unwrap();
+  }
+}
@@ -11,10 +11,10 @@
 
 use std::fs;
 use path_slash::PathBufExt;
+use path_slash::PathExt;
 
-pub fn show_no_format(commit: &String, name_only: bool, find_renames: &str, diff_filter: &str, show_dst: &Vec<&Option<&std::path::PathBuf>>, exclude_show_dst: bool) -> std::process::Output {
-    let cmd: (&str, &str) = if cfg!(target_os = "windows") { ("cmd", "/c") } else { ("sh", "-c") };
-    let show_name = if name_only { "--name-only" } else { "--name-status" };
+/// Pathspec of dirs to show, or to exclude
+fn pathspec(show_dst: &[&Option<&std::path::PathBuf>], exclude_show_dst: bool) -> String {
     let mut show_files_or_not = false;
     for path in show_dst {
         if let Some(path) = path {
@@ -37,48 +37,64 @@ pub fn show_no_format(commit: &String, name_only: bool, find_renames: &str, diff
             }
         };
     }
+    show_files
+}
+
+pub fn show_no_format(commit: &String, name_only: bool, find_renames: &str, diff_filter: &str, show_dst: &Vec<&Option<&std::path::PathBuf>>, exclude_show_dst: bool) -> std::process::Output {
+    let cmd: (&str, &str) = if cfg!(target_os = "windows") { ("cmd", "/c") } else { ("sh", "-c") };
+    let show_name = if name_only { "--name-only" } else { "--name-status" };
+    let show_files = pathspec(show_dst, exclude_show_dst);
     let gitcmd = String::from(r#"git show --format="#) + " " + show_name + " " + "--find-renames=" + find_renames + " " + "--diff-filter=" + diff_filter + " " + commit + &show_files;
     std::process::Command::new(cmd.0).arg(cmd.1).arg(gitcmd).output().unwrap()
 }
 
+/// Copies of the commit, from any file of its parent even if it is not modified
+pub fn show_copies(commit: &str, find_copies: &str, show_dst: &[&Option<&std::path::PathBuf>], exclude_show_dst: bool) -> std::process::Output {
+    // git is run directly, commit and paths are passed as they are
+    let mut args = vec![String::from("show"), String::from("--format="), String::from("--name-status"), format!("--find-copies={}", find_copies), String::from("--find-copies-harder"), String::from("--diff-filter=C"), String::from(commit)];
+    let dirs = show_dst.iter().filter_map(|path| path.as_ref()).filter(|path| path.is_dir()).collect::<Vec<_>>();
+    if !dirs.is_empty() {
+        args.push(String::from("--"));
+        args.extend(dirs.iter().map(|path| String::from(if exclude_show_dst { ":!:" } else { "" }) + &path.to_slash().unwrap()));
+    }
+    std::process::Command::new("git").args(args).output().unwrap()
+}
+
+/// Similarity index of renames or copies, e.g. 75 or 75%, in percent as git wants
+pub fn similarity(pct: &str) -> Result<String, String> {
+    match pct.trim_end_matches('%').parse::<u8>() {
+        Ok(n) if n <= 100 => Ok(format!("{}%", n)),
+        _ => Err(format!("invalid similarity: {}, want 0% to 100%", pct)),
+    }
+}
+
 pub fn diff_no_format(old_commit: &String, new_commit: &String, name_only: bool, find_renames: &str, diff_filter: &str, show_dst: &Vec<&Option<&std::path::PathBuf>>, exclude_show_dst: bool) -> std::process::Output {
     let cmd: (&str, &str) = if cfg!(target_os = "windows") { ("cmd", "/c") } else { ("sh", "-c") };
     let show_name = if name_only { "--name-only" } else { "--name-status" };
-    let mut show_files_or_not = false;
-    for path in show_dst {
-        if let Some(path) = path {
-            if path.is_dir() {
-                show_files_or_not = true;
-            }
-        }
-    };
-    let mut show_files = if show_files_or_not {
-        String::new() + " " + "--" + " "
-    } else {
-        String::new()
-    };
-    if show_files_or_not {
-        for path in show_dst {
-            if let Some(path) = path {
-                if path.is_dir() {
-                    show_files = show_files + if exclude_show_dst { ":!:" } else { "" } + &path.to_slash().unwrap() + " ";
-                }
-            }
-        };
-    }
+    let show_files = pathspec(show_dst, exclude_show_dst);
     let gitcmd = String::from(r#"git diff --format="#) + " " + show_name + " " + "--find-renames=" + find_renames + " " + "--diff-filter=" + diff_filter + " " + old_commit + " " + new_commit + &show_files;
     std::process::Command::new(cmd.0).arg(cmd.1).arg(gitcmd).output().unwrap()
 }
 
-pub fn create_file_from(commit: &String, fsrc: &std::path::PathBuf, fdst: &std::path::PathBuf, rcommit: Option<&str>) {
+pub fn show_file(commit: &str, fsrc: &std::path::PathBuf) -> Option<Vec<u8>> {
     let cmd: (&str, &str) = if cfg!(target_os = "windows") { ("cmd", "/c") } else { ("sh", "-c") };
-    let output = std::process::Command::new(cmd.0).arg(cmd.1).arg("git show").arg(String::from(commit) + if let Some(r) = rcommit { r } else { "" } + ":" + &fsrc.to_slash().unwrap()).output().unwrap();
+    let gitcmd = String::from("git show") + " " + commit + ":" + &fsrc.to_slash().unwrap();
+    let output = std::process::Command::new(cmd.0).arg(cmd.1).arg(gitcmd).output().unwrap();
     if output.status.success() {
+        Some(output.stdout)
+    } else {
+        None
+    }
+}
+
+pub fn create_file_from(commit: &String, fsrc: &std::path::PathBuf, fdst: &std::path::PathBuf, rcommit: Option<&str>) {
+    let commit = String::from(commit) + if let Some(r) = rcommit { r } else { "" };
+    if let Some(stdout) = show_file(&commit, fsrc) {
         let fdst_parent = fdst.parent().unwrap();
         if !fdst_parent.is_dir() {
             fs::create_dir_all(&fdst_parent).unwrap();
         }
-        fs::write(&fdst, output.stdout).unwrap();
+        fs::write(&fdst, stdout).unwrap();
     } else {
         println!("create_file_from_git: fsrc: {:?}", fsrc);
     }
@@ -92,14 +108,119 @@ pub fn revert_no_commit(commit: &String) {
     }
 }
 
-pub fn reset_hard_and_clean_xfd(commit: &String) {
+pub fn reset_hard_and_clean_xfd(commit: &str) {
     let cmd: (&str, &str) = if cfg!(target_os = "windows") { ("cmd", "/c") } else { ("sh", "-c") };
-    let output = std::process::Command::new(cmd.0).arg(cmd.1).arg("git reset --hard").arg(commit).output().unwrap();
+    let output = std::process::Command::new(cmd.0).arg(cmd.1).arg(String::from("git reset --hard ") + commit).output().unwrap();
     if !output.status.success() {
         println!("reset_hard_and_clean_xfd.1: {:#?}", output);
     }
-    let output = std::process::Command::new(cmd.0).arg(cmd.1).arg("git clean   -xfd").output().unwrap();
+    // 0.fwto is kept, where sessions and history of fwto are
+    let output = std::process::Command::new(cmd.0).arg(cmd.1).arg(String::from("git clean   -xfd -e /") + crate::audk::FWTO_WS).output().unwrap();
     if !output.status.success() {
         println!("reset_hard_and_clean_xfd.2: {:#?}", output);
     }
 }
+
+/// Commit all changes of workspace, true if it is committed
+pub fn commit_all(message: &str) -> bool {
+    let output = std::process::Command::new("git").args(["add", "-A"]).output().unwrap();
+    if !output.status.success() {
+        println!("commit_all.1: {:#?}", output);
+        return false;
+    }
+    // message is passed as it is, no shell is between to expand it
+    let output = std::process::Command::new("git").args(["commit", "-q", "-m", message]).output().unwrap();
+    if !output.status.success() {
+        println!("commit_all.2: {:#?}", output);
+        return false;
+    }
+    true
+}
+
+pub fn version() -> Option<String> {
+    let cmd: (&str, &str) = if cfg!(target_os = "windows") { ("cmd", "/c") } else { ("sh", "-c") };
+    let output = std::process::Command::new(cmd.0).arg(cmd.1).arg("git --version").output().ok()?;
+    if output.status.success() {
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        None
+    }
+}
+
+pub fn status_porcelain() -> Option<String> {
+    let cmd: (&str, &str) = if cfg!(target_os = "windows") { ("cmd", "/c") } else { ("sh", "-c") };
+    let output = std::process::Command::new(cmd.0).arg(cmd.1).arg("git status --porcelain").output().ok()?;
+    if output.status.success() {
+        Some(String::from_utf8_lossy(&output.stdout).to_string())
+    } else {
+        None
+    }
+}
+
+pub fn diff_numstat_no_index(old: &std::path::PathBuf, new: &std::path::PathBuf) -> Option<(String, String)> {
+    let cmd: (&str, &str) = if cfg!(target_os = "windows") { ("cmd", "/c") } else { ("sh", "-c") };
+    let gitcmd = String::from("git diff --no-index --numstat") + " " + r#"""# + &old.to_slash().unwrap() + r#"""# + " " + r#"""# + &new.to_slash().unwrap() + r#"""#;
+    let output = std::process::Command::new(cmd.0).arg(cmd.1).arg(gitcmd).output().ok()?;
+    // exit with 1 if there are differences
+    match output.status.code() {
+        Some(0) => Some((String::from("0"), String::from("0"))),
+        Some(1) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let mut part = stdout.split_whitespace();
+            Some((part.next()?.to_string(), part.next()?.to_string()))
+        },
+        _ => None,
+    }
+}
+
+pub fn diff_no_index(old: &std::path::PathBuf, new: &std::path::PathBuf) -> Option<String> {
+    let cmd: (&str, &str) = if cfg!(target_os = "windows") { ("cmd", "/c") } else { ("sh", "-c") };
+    let gitcmd = String::from("git diff --no-index --no-prefix") + " " + r#"""# + &old.to_slash().unwrap() + r#"""# + " " + r#"""# + &new.to_slash().unwrap() + r#"""#;
+    let output = std::process::Command::new(cmd.0).arg(cmd.1).arg(gitcmd).output().ok()?;
+    // exit with 1 if there are differences
+    match output.status.code() {
+        Some(0) | Some(1) => Some(String::from_utf8_lossy(&output.stdout).to_string()),
+        _ => None,
+    }
+}
+
+/// Three-way merge of local and remote from base, with merged result and whether it is clean
+pub fn merge_file(local: &std::path::Path, base: &std::path::Path, remote: &std::path::Path, labels: [&str; 3]) -> Option<(Vec<u8>, bool)> {
+    let cmd: (&str, &str) = if cfg!(target_os = "windows") { ("cmd", "/c") } else { ("sh", "-c") };
+    let mut gitcmd = String::from("git merge-file -p");
+    for label in labels.iter() {
+        gitcmd = gitcmd + " -L " + label;
+    }
+    for file in [local, base, remote].iter() {
+        gitcmd = gitcmd + " " + r#"""# + &file.to_slash().unwrap() + r#"""#;
+    }
+    let output = std::process::Command::new(cmd.0).arg(cmd.1).arg(gitcmd).output().ok()?;
+    // exit with number of conflicts up to 127, or -1 (255 of exit status) on error, e.g. binary files
+    match output.status.code() {
+        Some(0) => Some((output.stdout, true)),
+        Some(n) if n > 0 && n <= 127 => Some((output.stdout, false)),
+        _ => None,
+    }
+}
+
+pub fn rev_parse(commit: &str) -> Option<String> {
+    let cmd: (&str, &str) = if cfg!(target_os = "windows") { ("cmd", "/c") } else { ("sh", "-c") };
+    let output = std::process::Command::new(cmd.0).arg(cmd.1).arg(String::from("git rev-parse --verify ") + commit).output().ok()?;
+    if output.status.success() {
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        None
+    }
+}
+
+/// Branch of HEAD, or None if HEAD is detached
+pub fn branch() -> Option<String> {
+    let cmd: (&str, &str) = if cfg!(target_os = "windows") { ("cmd", "/c") } else { ("sh", "-c") };
+    let output = std::process::Command::new(cmd.0).arg(cmd.1).arg("git rev-parse --abbrev-ref HEAD").output().ok()?;
+    let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if output.status.success() && branch != "HEAD" {
+        Some(branch)
+    } else {
+        None
+    }
+}
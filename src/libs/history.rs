@@ -0,0 +1,151 @@
+/*++ @file
+
+  Copyright ©2021 Liu Yi, liuyi28@lenovo.com
+
+  This program is just made available under the terms and conditions of the
+  MIT license: http://www.efikarl.com/mit-license.html
+
+  THE PROGRAM IS DISTRIBUTED UNDER THE MIT LICENSE ON AN "AS IS" BASIS,
+  WITHOUT WARRANTIES OR REPRESENTATIONS OF ANY KIND, EITHER EXPRESS OR IMPLIED.
+--*/
+
+use std::fs;
+use std::io::prelude::*;
+use serde::{Serialize, Deserialize};
+
+/// Number of builds in the trends
+const TRENDS            : usize = 20;
+/// Number of modules in the slowest ones
+const SLOWEST           : usize = 10;
+
+/// Wall-clock seconds of a hook, make or a module
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Timing {
+    pub name            : String,
+    pub secs            : f64,
+}
+
+/// One build, as a line of the history
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Record {
+    /// Start of the build in UTC, as yyyymmdd-hhmmss
+    pub date            : String,
+    pub commit          : Option<String>,
+    pub branch          : Option<String>,
+    /// Variant of --matrix, or veb of the build
+    pub variant         : String,
+    pub make_args       : Vec<String>,
+    pub status          : Option<i32>,
+    /// Seconds of the whole build, with hooks
+    pub total           : f64,
+    pub make            : f64,
+    pub hooks           : Vec<Timing>,
+    /// Modules in the order they are started, each takes time until the next one is started
+    pub modules         : Vec<Timing>,
+}
+
+/// Module of a line of build log, e.g. "Building ... MdePkg/Library/BaseLib/BaseLib.inf [X64]"
+pub fn module_of(line: &str) -> Option<String> {
+    let module = line.trim().strip_prefix("Building ... ")?.trim();
+    let inf = module.split_whitespace().next()?;
+    if inf.to_ascii_lowercase().ends_with(".inf") {
+        Some(module.replace('\\', "/"))
+    } else {
+        None
+    }
+}
+
+/// Timings of modules from their starts, the last one takes time until the end
+pub fn modules(starts: &[Timing], end: f64) -> Vec<Timing> {
+    starts.iter().enumerate().map(|(i, start)| {
+        let next = starts.get(i + 1).map(|next| next.secs).unwrap_or(end);
+        Timing { name: start.name.clone(), secs: next - start.secs }
+    }).collect()
+}
+
+pub fn append(history: &std::path::Path, record: &Record) {
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(history).unwrap();
+    writeln!(file, "{}", serde_json::to_string(record).unwrap()).unwrap();
+}
+
+/// Records of the history, with invalid lines skipped
+pub fn load(history: &std::path::Path) -> Vec<Record> {
+    let lines = match fs::read(history) {
+        Ok(buf) => String::from_utf8_lossy(&buf).to_string(),
+        Err(_) => return Vec::new(),
+    };
+    lines.lines().filter_map(|line| serde_json::from_str(line).ok()).collect()
+}
+
+fn status_name(status: Option<i32>) -> String {
+    match status {
+        Some(0)    => String::from("passed"),
+        Some(code) => format!("failed({})", code),
+        None       => String::from("terminated"),
+    }
+}
+
+fn delta(secs: f64, prev: Option<f64>) -> String {
+    match prev {
+        Some(prev) => format!("{:+.1}s", secs - prev),
+        None       => String::from("-"),
+    }
+}
+
+/// Print make time of recent builds against the previous build of the same variant,
+/// then the slowest modules of the last build of every variant against its previous one
+pub fn print(history: &std::path::Path) {
+    let records = load(history);
+    if records.is_empty() {
+        println!("WRN: no build is in history: {:?}", history);
+        return;
+    }
+    let previous = |i: usize| records[..i].iter().rev().find(|r| r.variant == records[i].variant);
+    println!("INF: build history {:?}", history);
+    println!("  {:<16} {:<9} {:<12} {:>8} {:>8} {:>8}  variant", "date", "commit", "status", "make", "delta", "total");
+    for (i, record) in records.iter().enumerate().skip(records.len().saturating_sub(TRENDS)) {
+        let commit = record.commit.as_ref().map(|c| &c[..c.len().min(8)]).unwrap_or("unknown");
+        println!("  {:<16} {:<9} {:<12} {:>7.1}s {:>8} {:>7.1}s  {}", record.date, commit, status_name(record.status), record.make, delta(record.make, previous(i).map(|r| r.make)), record.total, record.variant);
+    }
+
+    let mut variants: Vec<&String> = Vec::new();
+    for (i, record) in records.iter().enumerate().rev() {
+        if variants.contains(&&record.variant) {
+            continue;
+        }
+        variants.push(&record.variant);
+        if record.modules.is_empty() {
+            continue;
+        }
+        let mut modules = record.modules.iter().collect::<Vec<_>>();
+        modules.sort_by(|a, b| b.secs.partial_cmp(&a.secs).unwrap_or(std::cmp::Ordering::Equal));
+        let prev = previous(i);
+        println!("---------------------------");
+        println!("INF: slowest modules of {} at {}, against {}", record.variant, record.date, prev.map(|r| r.date.as_str()).unwrap_or("none"));
+        for module in modules.iter().take(SLOWEST) {
+            let prev_secs = prev.and_then(|prev| prev.modules.iter().find(|m| m.name == module.name)).map(|m| m.secs);
+            println!("  {:>7.1}s {:>8}  {}", module.secs, delta(module.secs, prev_secs), module.name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn module_of_build_lines() {
+        assert_eq!(module_of("Building ... MdePkg/Library/BaseLib/BaseLib.inf [X64]"), Some(String::from("MdePkg/Library/BaseLib/BaseLib.inf [X64]")));
+        assert_eq!(module_of("  Building ... AmiPkg\\Csp\\CspLib.INF [IA32]  "), Some(String::from("AmiPkg/Csp/CspLib.INF [IA32]")));
+        assert_eq!(module_of("Building ... MdePkg/MdePkg.dec [X64]"), None);
+        assert_eq!(module_of("make: Building ... MdePkg/Mod.inf [X64]"), None);
+        assert_eq!(module_of("Building ... "), None);
+    }
+
+    #[test]
+    fn modules_take_time_until_the_next() {
+        let starts = vec![Timing { name: String::from("a"), secs: 1.0 }, Timing { name: String::from("b"), secs: 3.5 }];
+        let timings = modules(&starts, 10.0).iter().map(|t| (t.name.clone(), t.secs)).collect::<Vec<_>>();
+        assert_eq!(timings, vec![(String::from("a"), 2.5), (String::from("b"), 6.5)]);
+    }
+}
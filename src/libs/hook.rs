@@ -0,0 +1,268 @@
+/*++ @file
+
+  Copyright ©2021 Liu Yi, liuyi28@lenovo.com
+
+  This program is just made available under the terms and conditions of the
+  MIT license: http://www.efikarl.com/mit-license.html
+
+  THE PROGRAM IS DISTRIBUTED UNDER THE MIT LICENSE ON AN "AS IS" BASIS,
+  WITHOUT WARRANTIES OR REPRESENTATIONS OF ANY KIND, EITHER EXPRESS OR IMPLIED.
+--*/
+
+use std::fs;
+use serde::Serialize;
+use path_slash::PathBufExt;
+
+use crate::audk;
+
+const HOOK_HOME         : &str = "hook";
+
+/// Context of hooks, passed by FWTO_* environment variables and the json file of FWTO_CONTEXT
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Context {
+    pub stage           : String,
+    pub workspace       : std::path::PathBuf,
+    pub veb             : Option<std::path::PathBuf>,
+    /// Target of make, rebuild or all
+    pub build_mode      : Option<String>,
+    /// Exit code of make, for post_build only
+    pub build_status    : Option<i32>,
+    /// Log of make, for post_build only
+    pub build_log       : Option<std::path::PathBuf>,
+    /// Files affected by ovrd, cbup or view, relative to workspace
+    pub files           : Vec<std::path::PathBuf>,
+    /// Variables of the build, set over environment of fwto
+    #[serde(skip)]
+    pub env             : Vec<(String, std::ffi::OsString)>,
+}
+
+impl Context {
+    pub fn new(stage: &str) -> Self {
+        Self { stage: String::from(stage), workspace: std::env::current_dir().unwrap(), ..Default::default() }
+    }
+
+    fn envs(&self, list: &std::path::Path) -> Vec<(String, String)> {
+        let mut envs = vec![
+            (String::from("FWTO_STAGE"), self.stage.clone()),
+            (String::from("FWTO_WORKSPACE"), self.workspace.to_string_lossy().to_string()),
+        ];
+        if let Some(veb) = self.veb.as_ref() {
+            envs.push((String::from("FWTO_VEB"), veb.to_string_lossy().to_string()));
+        }
+        if let Some(build_mode) = self.build_mode.as_ref() {
+            envs.push((String::from("FWTO_BUILD_MODE"), build_mode.clone()));
+        }
+        if let Some(build_status) = self.build_status {
+            envs.push((String::from("FWTO_BUILD_STATUS"), build_status.to_string()));
+        }
+        if let Some(build_log) = self.build_log.as_ref() {
+            envs.push((String::from("FWTO_BUILD_LOG"), build_log.to_string_lossy().to_string()));
+        }
+        envs.push((String::from("FWTO_FILES"), list.to_string_lossy().to_string()));
+        envs
+    }
+
+    /// Write the context as json, and affected files one per line, and return their paths
+    fn write(&self) -> (std::path::PathBuf, std::path::PathBuf) {
+        let home = self.workspace.join(audk::FWTO_WS).join(HOOK_HOME);
+        fs::create_dir_all(&home).unwrap();
+        let json = home.join(format!("{}.json", self.stage));
+        fs::write(&json, serde_json::to_string_pretty(self).unwrap() + "\n").unwrap();
+        let list = home.join(format!("{}.files", self.stage));
+        fs::write(&list, self.files.iter().map(|f| f.to_slash().unwrap() + "\r\n").collect::<String>()).unwrap();
+        (json, list)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    Failed(Option<i32>),
+    TimedOut,
+    Missing,
+    Error(String),
+}
+
+impl Outcome {
+    fn name(&self) -> String {
+        match self {
+            Outcome::Passed          => String::from("passed"),
+            Outcome::Failed(Some(c)) => format!("failed({})", c),
+            Outcome::Failed(None)    => String::from("failed"),
+            Outcome::TimedOut        => String::from("timed-out"),
+            Outcome::Missing         => String::from("missing"),
+            Outcome::Error(_)        => String::from("error"),
+        }
+    }
+}
+
+/// One hook that ran, or was to run
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub stage           : String,
+    pub hook            : String,
+    pub outcome         : Outcome,
+    pub on_failure      : audk::OnFailure,
+    pub elapsed         : std::time::Duration,
+}
+
+/// Wait for the child, and kill it once timeout
+fn wait(child: &mut std::process::Child, timeout: Option<u64>) -> Outcome {
+    let start = std::time::Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                return if status.success() { Outcome::Passed } else { Outcome::Failed(status.code()) };
+            },
+            Ok(None) => {
+                if let Some(timeout) = timeout {
+                    if start.elapsed() >= std::time::Duration::from_secs(timeout) {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        return Outcome::TimedOut;
+                    }
+                }
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            },
+            Err(e) => return Outcome::Error(e.to_string()),
+        }
+    }
+}
+
+/// Program on PATH, with extensions of PATHEXT on windows
+pub fn which(program: &std::path::Path) -> Option<std::path::PathBuf> {
+    let exts = if cfg!(target_os = "windows") {
+        std::env::var("PATHEXT").unwrap_or_else(|_| String::from(".COM;.EXE;.BAT;.CMD")).split(';').map(String::from).collect::<Vec<_>>()
+    } else {
+        Vec::new()
+    };
+    let candidates = |dir: &std::path::Path| {
+        let mut candidates = vec![dir.join(program)];
+        if program.extension().is_none() {
+            candidates.extend(exts.iter().map(|ext| dir.join(program.to_string_lossy().to_string() + &ext.to_ascii_lowercase())));
+        }
+        candidates
+    };
+    // a path is taken as it is, and only a name is looked up on PATH
+    if program.components().count() > 1 {
+        return candidates(&std::env::current_dir().unwrap()).into_iter().find(|p| p.is_file());
+    }
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path).flat_map(|dir| candidates(&dir)).find(|p| p.is_file())
+}
+
+fn run_one(hook: &audk::ScriptsDesc, context: &Context, json: &std::path::Path, list: &std::path::Path) -> Outcome {
+    // inline command is run by the shell, unless interpreter is given
+    let shell: (&str, &str) = if cfg!(target_os = "windows") { ("cmd", "/c") } else { ("sh", "-c") };
+    let (interpreter, farg, last) = match (&hook.file, &hook.run) {
+        (Some(_), Some(_)) => return Outcome::Error(String::from("both file and run are given, keep one of them")),
+        (None, None) => return Outcome::Error(String::from("neither file nor run is given")),
+        (Some(file), None) => {
+            // file is relative to workspace, even if the hook runs in another dir
+            let file = context.workspace.join(file);
+            if !file.is_file() {
+                return Outcome::Missing;
+            }
+            match hook.interpreter.as_ref() {
+                Some(interpreter) => (interpreter.clone(), hook.farg.clone(), file.into_os_string()),
+                None => return Outcome::Error(String::from("interpreter of file is not given")),
+            }
+        },
+        (None, Some(run)) => match hook.interpreter.as_ref() {
+            Some(interpreter) => (interpreter.clone(), hook.farg.clone(), std::ffi::OsString::from(run)),
+            None => (std::path::PathBuf::from(shell.0), Some(String::from(shell.1)), std::ffi::OsString::from(run)),
+        },
+    };
+    let program = match which(&interpreter) {
+        Some(program) => program,
+        None => {
+            let os = if cfg!(target_os = "windows") { "windows" } else { "linux" };
+            return Outcome::Error(format!("interpreter {:?} is not found on PATH, install it, give its full path, or give a {} variant of the hook", interpreter, os));
+        }
+    };
+    let mut command = std::process::Command::new(program);
+    if let Some(args) = hook.args.as_ref() {
+        command.args(args);
+    }
+    if let Some(farg) = farg.as_ref() {
+        command.arg(farg);
+    }
+    command.arg(last);
+    command.current_dir(hook.cwd.as_ref().unwrap_or(&context.workspace));
+    command.envs(context.env.iter().map(|(key, value)| (key, value)));
+    command.envs(context.envs(list));
+    command.env("FWTO_CONTEXT", json);
+    if let Some(env) = hook.env.as_ref() {
+        command.envs(env);
+    }
+    match command.spawn() {
+        Ok(mut child) => wait(&mut child, hook.timeout),
+        Err(e) => Outcome::Error(format!("failed to launch {:?}: {}", interpreter, e)),
+    }
+}
+
+/// Run hooks of the stage in order, false if one fails and wants to abort
+pub fn run(hooks: &Option<Vec<audk::ScriptsDesc>>, context: &Context, reports: &mut Vec<Report>) -> bool {
+    let hooks = match hooks {
+        Some(hooks) if !hooks.is_empty() => hooks,
+        _ => return true,
+    };
+    let (json, list) = context.write();
+    for hook in hooks {
+        let hook = hook.for_os();
+        let name = match (&hook.file, &hook.run) {
+            (Some(file), _) => file.to_string_lossy().to_string(),
+            (None, Some(run)) => run.clone(),
+            (None, None) => String::from("-"),
+        };
+        let on_failure = hook.on_failure.unwrap_or(audk::OnFailure::Warn);
+        println!("INF: hook {}: {}", context.stage, name);
+        let start = std::time::Instant::now();
+        let outcome = run_one(&hook, context, &json, &list);
+        let report = Report { stage: context.stage.clone(), hook: name, outcome, on_failure, elapsed: start.elapsed() };
+        if report.outcome != Outcome::Passed {
+            let detail = match &report.outcome {
+                Outcome::Error(e) => format!(": {}", e),
+                _ => String::new(),
+            };
+            match on_failure {
+                audk::OnFailure::Abort  => println!("ERR: hook {} {:?} {}{}, abort", report.stage, report.hook, report.outcome.name(), detail),
+                audk::OnFailure::Warn   => println!("WRN: hook {} {:?} {}{}", report.stage, report.hook, report.outcome.name(), detail),
+                audk::OnFailure::Ignore => {},
+            }
+        }
+        let abort = report.outcome != Outcome::Passed && on_failure == audk::OnFailure::Abort;
+        reports.push(report);
+        if abort {
+            return false;
+        }
+    }
+    true
+}
+
+/// Run hooks of the stage of an operation with its affected files, false if one vetoes the operation
+pub fn run_stage(scripts: &Option<&audk::Scripts>, stage: &str, files: &[std::path::PathBuf]) -> bool {
+    let scripts = match scripts {
+        Some(scripts) => scripts,
+        None => return true,
+    };
+    let mut context = Context::new(stage);
+    context.files = files.to_vec();
+    let mut reports = Vec::new();
+    let result = run(scripts.hooks(stage), &context, &mut reports);
+    summary(&reports);
+    result
+}
+
+/// Print every hook that ran
+pub fn summary(reports: &[Report]) {
+    if reports.is_empty() {
+        return;
+    }
+    println!("---------------------------");
+    println!("INF: hooks");
+    for report in reports {
+        let policy = if report.outcome == Outcome::Passed { String::new() } else { format!("  [{:?}]", report.on_failure).to_ascii_lowercase() };
+        println!("  {:<12} {:<12} {:>7.1}s  {}{}", report.stage, report.outcome.name(), report.elapsed.as_secs_f64(), report.hook, policy);
+    }
+}
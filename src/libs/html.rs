@@ -0,0 +1,172 @@
+/*++ @file
+
+  Copyright ©2021 Liu Yi, liuyi28@lenovo.com
+
+  This program is just made available under the terms and conditions of the
+  MIT license: http://www.efikarl.com/mit-license.html
+
+  THE PROGRAM IS DISTRIBUTED UNDER THE MIT LICENSE ON AN "AS IS" BASIS,
+  WITHOUT WARRANTIES OR REPRESENTATIONS OF ANY KIND, EITHER EXPRESS OR IMPLIED.
+--*/
+
+/// Style of the page, which is inline to work offline
+pub const STYLE: &str = r#"
+body { font-family: Segoe UI, Helvetica, Arial, sans-serif; margin: 2em; color: #24292e; }
+h2 { border-bottom: 1px solid #d0d7de; padding-bottom: .3em; }
+a { color: #0969da; text-decoration: none; }
+.stat-add { color: #1a7f37; } .stat-del { color: #cf222e; }
+.file { border: 1px solid #d0d7de; border-radius: 6px; margin: 1.5em 0; }
+.file h3 { margin: 0; padding: .5em 1em; background: #f6f8fa; border-bottom: 1px solid #d0d7de; font-size: 1em; }
+.file .labels { padding: .3em 1em; margin: 0; color: #57606a; font-size: .85em; }
+table.diff { width: 100%; border-collapse: collapse; table-layout: fixed; font-family: Consolas, Menlo, monospace; font-size: 12px; }
+table.diff td { padding: 0 .5em; vertical-align: top; white-space: pre-wrap; word-wrap: break-word; }
+table.diff td.ln { width: 4em; text-align: right; color: #8c959f; user-select: none; }
+table.diff tr.hunk td { background: #ddf4ff; color: #57606a; }
+td.del { background: #ffebe9; } td.add { background: #e6ffec; } td.nil { background: #f6f8fa; }
+td.del span.x { background: #ffc1c0; } td.add span.x { background: #abf2bc; }
+"#;
+
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&'  => escaped += "&amp;",
+            '<'  => escaped += "&lt;",
+            '>'  => escaped += "&gt;",
+            '"'  => escaped += "&quot;",
+            '\r' => {},
+            c    => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Escape old and new line, and mark their differences between common prefix and suffix
+fn intraline(old: &str, new: &str) -> (String, String) {
+    let old_chars = old.chars().collect::<Vec<_>>();
+    let new_chars = new.chars().collect::<Vec<_>>();
+    let prefix = old_chars.iter().zip(new_chars.iter()).take_while(|(a, b)| a == b).count();
+    let suffix = old_chars[prefix..].iter().rev().zip(new_chars[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let mark = |chars: &[char]| {
+        let head = chars[..prefix].iter().collect::<String>();
+        let diff = chars[prefix..chars.len() - suffix].iter().collect::<String>();
+        let tail = chars[chars.len() - suffix..].iter().collect::<String>();
+        if diff.is_empty() {
+            escape(&(head + &tail))
+        } else {
+            escape(&head) + r#"<span class="x">"# + &escape(&diff) + "</span>" + &escape(&tail)
+        }
+    };
+    (mark(&old_chars), mark(&new_chars))
+}
+
+fn row(old: Option<(usize, &str, &str)>, new: Option<(usize, &str, &str)>) -> String {
+    let cell = |side: Option<(usize, &str, &str)>| match side {
+        Some((ln, class, html)) => format!(r#"<td class="ln">{}</td><td class="{}">{}</td>"#, ln, class, html),
+        None => String::from(r#"<td class="ln"></td><td class="nil"></td>"#),
+    };
+    String::from("<tr>") + &cell(old) + &cell(new) + "</tr>\n"
+}
+
+/// Rows of a side-by-side table from unified diff, with the number of added and deleted lines
+pub fn side_by_side(diff: &str) -> (String, usize, usize) {
+    let mut rows = String::new();
+    let (mut adds, mut dels) = (0, 0);
+    let (mut old_ln, mut new_ln) = (0, 0);
+    let mut old_run: Vec<&str> = Vec::new();
+    let mut new_run: Vec<&str> = Vec::new();
+    let mut in_hunks = false;
+
+    // pair deleted and added lines of a change row by row
+    let flush = |rows: &mut String, old_run: &mut Vec<&str>, new_run: &mut Vec<&str>, old_ln: &mut usize, new_ln: &mut usize| {
+        for i in 0..old_run.len().max(new_run.len()) {
+            match (old_run.get(i), new_run.get(i)) {
+                (Some(old), Some(new)) => {
+                    let (old, new) = intraline(old, new);
+                    *rows += &row(Some((*old_ln, "del", &old)), Some((*new_ln, "add", &new)));
+                    *old_ln += 1;
+                    *new_ln += 1;
+                },
+                (Some(old), None) => {
+                    *rows += &row(Some((*old_ln, "del", &escape(old))), None);
+                    *old_ln += 1;
+                },
+                (None, Some(new)) => {
+                    *rows += &row(None, Some((*new_ln, "add", &escape(new))));
+                    *new_ln += 1;
+                },
+                (None, None) => {},
+            }
+        }
+        old_run.clear();
+        new_run.clear();
+    };
+
+    for line in diff.lines() {
+        if line.starts_with("@@") {
+            flush(&mut rows, &mut old_run, &mut new_run, &mut old_ln, &mut new_ln);
+            in_hunks = true;
+            // @@ -old_ln,n +new_ln,n @@
+            let mut part = line.split_whitespace().skip(1);
+            let start = |range: Option<&str>| range.and_then(|r| r[1..].split(',').next()?.parse::<usize>().ok()).unwrap_or(0);
+            old_ln = start(part.next()).max(1);
+            new_ln = start(part.next()).max(1);
+            rows += &format!(r#"<tr class="hunk"><td class="ln"></td><td colspan="3">{}</td></tr>"#, escape(line));
+            rows += "\n";
+            continue;
+        }
+        if !in_hunks {
+            continue;
+        }
+        if let Some(old) = line.strip_prefix('-') {
+            dels += 1;
+            old_run.push(old);
+        } else if let Some(new) = line.strip_prefix('+') {
+            adds += 1;
+            new_run.push(new);
+        } else if let Some(same) = line.strip_prefix(' ') {
+            flush(&mut rows, &mut old_run, &mut new_run, &mut old_ln, &mut new_ln);
+            let same = escape(same);
+            rows += &row(Some((old_ln, "", &same)), Some((new_ln, "", &same)));
+            old_ln += 1;
+            new_ln += 1;
+        }
+    }
+    flush(&mut rows, &mut old_run, &mut new_run, &mut old_ln, &mut new_ln);
+    (rows, adds, dels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intraline_marks_the_middle() {
+        assert_eq!(intraline("int a = 1;", "int a = 22;"), (
+            String::from(r#"int a = <span class="x">1</span>;"#),
+            String::from(r#"int a = <span class="x">22</span>;"#),
+        ));
+        // inserted only, old side is not marked
+        assert_eq!(intraline("ab", "a<b"), (String::from("ab"), String::from(r#"a<span class="x">&lt;</span>b"#)));
+        assert_eq!(intraline("same", "same"), (String::from("same"), String::from("same")));
+    }
+
+    #[test]
+    fn side_by_side_pairs_changes() {
+        let diff = "--- a/x.c\n+++ b/x.c\n@@ -3,4 +3,3 @@ f\n keep\n-int a = 1;\n-gone\n+int a = 2;\n";
+        let (rows, adds, dels) = side_by_side(diff);
+        assert_eq!((adds, dels), (1, 2));
+        let rows = rows.lines().collect::<Vec<_>>();
+        assert_eq!(rows.len(), 4);
+        assert!(rows[0].starts_with(r#"<tr class="hunk">"#) && rows[0].contains("@@ -3,4 +3,3 @@ f"));
+        assert_eq!(rows[1], r#"<tr><td class="ln">3</td><td class="">keep</td><td class="ln">3</td><td class="">keep</td></tr>"#);
+        assert_eq!(rows[2], r#"<tr><td class="ln">4</td><td class="del">int a = <span class="x">1</span>;</td><td class="ln">4</td><td class="add">int a = <span class="x">2</span>;</td></tr>"#);
+        assert_eq!(rows[3], r#"<tr><td class="ln">5</td><td class="del">gone</td><td class="ln"></td><td class="nil"></td></tr>"#);
+    }
+
+    #[test]
+    fn side_by_side_skips_headers() {
+        let (rows, adds, dels) = side_by_side("diff --git a/x b/x\n--- a/x\n+++ b/x\n");
+        assert_eq!((rows.as_str(), adds, dels), ("", 0, 0));
+    }
+}
@@ -0,0 +1,146 @@
+/*++ @file
+
+  Copyright ©2021 Liu Yi, liuyi28@lenovo.com
+
+  This program is just made available under the terms and conditions of the
+  MIT license: http://www.efikarl.com/mit-license.html
+
+  THE PROGRAM IS DISTRIBUTED UNDER THE MIT LICENSE ON AN "AS IS" BASIS,
+  WITHOUT WARRANTIES OR REPRESENTATIONS OF ANY KIND, EITHER EXPRESS OR IMPLIED.
+--*/
+
+use std::fs;
+use path_slash::PathBufExt;
+
+use crate::libs::ffs;
+
+fn to_path(file: &str) -> std::path::PathBuf {
+    std::path::PathBuf::from_slash(file.replace('\\', "/"))
+}
+
+/// Files of [Sources] and [Sources.<arch>] sections of inf text, as they are written
+fn source_files(text: &str) -> Vec<String> {
+    let mut files = Vec::new();
+    let mut in_sources = false;
+    let mut joined = String::new();
+    for line in text.lines() {
+        let line = line.split('#').next().unwrap().trim_end();
+        // a line ended with \ is continued by the next line
+        if let Some(line) = line.strip_suffix('\\') {
+            joined.push_str(line);
+            joined.push(' ');
+            continue;
+        }
+        joined.push_str(line);
+        let whole = std::mem::take(&mut joined);
+        let line = whole.trim();
+        if line.is_empty() {
+            continue;
+        }
+        // [Sources], [Sources.X64], [Sources.IA32, Sources.X64]
+        if line.starts_with('[') && line.ends_with(']') {
+            in_sources = line.trim_matches(|c| c == '[' || c == ']').split(',').any(|section| {
+                let section = section.trim().to_ascii_lowercase();
+                section == "sources" || section.starts_with("sources.")
+            });
+            continue;
+        }
+        if !in_sources {
+            continue;
+        }
+        // File.c | GCC
+        files.push(String::from(line.split('|').next().unwrap().trim()));
+    }
+    files
+}
+
+/// Files of [Sources] and [Sources.<arch>] sections, relative to workspace
+pub fn sources(inf: &std::path::Path) -> Vec<std::path::PathBuf> {
+    let mut sources = Vec::new();
+    let buf = match fs::read(inf) {
+        Ok(buf) => buf,
+        Err(_)  => return sources,
+    };
+    let dir = inf.parent().unwrap();
+    for file in source_files(&String::from_utf8_lossy(&buf)) {
+        if file.contains("$(") {
+            println!("WRN: macro in sources is unsupported: {:?}", file);
+            continue;
+        }
+        sources.push(ffs::normalize(dir.join(to_path(&file))));
+    }
+    sources
+}
+
+/// Files of #include "..." in files, which are in dir of the file or of the inf, recursively
+pub fn includes(files: &[std::path::PathBuf], inf: &std::path::Path) -> Vec<std::path::PathBuf> {
+    let mut includes: Vec<std::path::PathBuf> = Vec::new();
+    let mut pending = files.to_vec();
+    while let Some(file) = pending.pop() {
+        let buf = match fs::read(&file) {
+            Ok(buf) => buf,
+            Err(_)  => continue,
+        };
+        for line in String::from_utf8_lossy(&buf).lines() {
+            let line = line.trim();
+            if !line.starts_with('#') || !line[1..].trim_start().starts_with("include") {
+                continue;
+            }
+            let name = match line.split('"').nth(1) {
+                Some(name) => to_path(name),
+                None => continue,
+            };
+            let found = [file.parent().unwrap(), inf.parent().unwrap()].iter()
+                .map(|dir| ffs::normalize(dir.join(&name)))
+                .find(|include| include.is_file());
+            if let Some(include) = found {
+                if !files.contains(&include) && !includes.contains(&include) {
+                    includes.push(include.clone());
+                    pending.push(include);
+                }
+            }
+        }
+    }
+    includes
+}
+
+/// The inf, its sources and includes, which are files of a module
+pub fn module_files(inf: &std::path::Path) -> Vec<std::path::PathBuf> {
+    let mut files = vec![ffs::normalize(inf)];
+    for source in sources(inf) {
+        if !files.contains(&source) {
+            files.push(source);
+        }
+    }
+    let includes = includes(&files[1..], inf);
+    files.extend(includes);
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sections() {
+        let text = "[Defines]\n  BASE_NAME = Mod\n[Sources]\n  Mod.c\n[sources.X64]\n  X64/Arch.c\n[Sources.IA32, Sources.X64]\n  Both.c\n[LibraryClasses]\n  BaseLib\n[Packages.X64, Sources.EBC]\n  Ebc.c\n";
+        assert_eq!(source_files(text), vec!["Mod.c", "X64/Arch.c", "Both.c", "Ebc.c"]);
+    }
+
+    #[test]
+    fn comments() {
+        let text = "# [Sources]\n[Sources] # of all archs\n  # Old.c\n  Mod.c # main\n  Gcc.c | GCC\n\n  Inc\\Mod.h\n";
+        assert_eq!(source_files(text), vec!["Mod.c", "Gcc.c", "Inc\\Mod.h"]);
+    }
+
+    #[test]
+    fn continuations() {
+        let text = "[LibraryClasses, \\\n Sources.X64]\n  Mod.c \\\n    | MSFT\n  Next.c\n[Defines]\n  Other.c\n";
+        assert_eq!(source_files(text), vec!["Mod.c", "Next.c"]);
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(to_path("Inc\\Mod.h"), std::path::PathBuf::from_slash("Inc/Mod.h"));
+    }
+}
@@ -9,5 +9,13 @@
   WITHOUT WARRANTIES OR REPRESENTATIONS OF ANY KIND, EITHER EXPRESS OR IMPLIED.
 --*/
 
+pub mod cif;
 pub mod ffs;
-pub mod git;
\ No newline at end of file
+pub mod git;
+pub mod history;
+pub mod hook;
+pub mod html;
+pub mod inf;
+pub mod sha256;
+pub mod tool;
+pub mod toolkit;
\ No newline at end of file
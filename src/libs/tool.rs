@@ -0,0 +1,63 @@
+/*++ @file
+
+  Copyright ©2021 Liu Yi, liuyi28@lenovo.com
+
+  This program is just made available under the terms and conditions of the
+  MIT license: http://www.efikarl.com/mit-license.html
+
+  THE PROGRAM IS DISTRIBUTED UNDER THE MIT LICENSE ON AN "AS IS" BASIS,
+  WITHOUT WARRANTIES OR REPRESENTATIONS OF ANY KIND, EITHER EXPRESS OR IMPLIED.
+--*/
+
+use std::fs;
+
+use crate::libs::ffs;
+
+/// Args of the template, with {base}, {local}, {remote} and {merged} replaced by absolute path of files
+pub fn expand(args: &[String], files: &[(&str, &std::path::Path)]) -> Vec<String> {
+    let cwd = std::env::current_dir().unwrap();
+    args.iter().map(|arg| {
+        let mut arg = arg.clone();
+        for (name, file) in files {
+            arg = arg.replace(&format!("{{{}}}", name), &cwd.join(file).to_string_lossy());
+        }
+        arg
+    }).collect()
+}
+
+/// Launch the tool and wait for it, None if it can not be launched
+pub fn launch(cmd: &std::path::Path, args: &[String], files: &[(&str, &std::path::Path)]) -> Option<std::process::ExitStatus> {
+    match std::process::Command::new(cmd).args(expand(args, files)).status() {
+        Ok(status) => Some(status),
+        Err(e) => {
+            println!("ERR: failed to launch {:?}: {}", cmd, e);
+            None
+        }
+    }
+}
+
+/// Files under dir, relative to it
+pub fn files_under(dir: &std::path::Path) -> Vec<std::path::PathBuf> {
+    let mut files = Vec::new();
+    if dir.is_dir() {
+        ffs::walk(dir, &mut |path, is_dir| {
+            if !is_dir {
+                files.push(path.strip_prefix(dir).unwrap().to_path_buf());
+            }
+            is_dir
+        });
+    }
+    files
+}
+
+/// Append resolved files to the log if they are not there, one file per line
+pub fn record(log: &std::path::Path, files: &[std::path::PathBuf]) {
+    let mut text = fs::read_to_string(log).unwrap_or_default();
+    for file in files {
+        let line = file.to_string_lossy();
+        if !text.lines().any(|l| l == line) {
+            text += &format!("{}\r\n", line);
+        }
+    }
+    fs::write(log, text).unwrap();
+}
@@ -0,0 +1,273 @@
+/*++ @file
+
+  Copyright ©2021 Liu Yi, liuyi28@lenovo.com
+
+  This program is just made available under the terms and conditions of the
+  MIT license: http://www.efikarl.com/mit-license.html
+
+  THE PROGRAM IS DISTRIBUTED UNDER THE MIT LICENSE ON AN "AS IS" BASIS,
+  WITHOUT WARRANTIES OR REPRESENTATIONS OF ANY KIND, EITHER EXPRESS OR IMPLIED.
+--*/
+
+use std::fs;
+use path_slash::PathBufExt;
+
+use crate::audk;
+use crate::libs::ffs;
+use crate::libs::hook;
+use crate::libs::sha256;
+
+/// Common locations where EWDK, BuildTools and python are installed, which are walked
+const TOOLKIT_ROOTS     : [&str; 3] = ["C:/EfiKa/FwTools", "C:/Tools", "C:/EWDK"];
+/// Drives where EWDK iso is mounted, which are checked themselves only
+const TOOLKIT_MOUNTS    : [&str; 4] = ["D:/", "E:/", "F:/", "G:/"];
+const TOOLKIT_DEPTH     : usize = 4;
+/// Roots to be searched besides those of json, separated as PATH
+const TOOLKIT_ROOTS_ENV : &str = "FWTO_TOOLKIT_ROOTS";
+/// Names of python, in the order of preference
+const PYTHON_NAMES      : [&str; 4] = ["python.exe", "python3.exe", "python3", "python"];
+/// Where versions of EWDK are, e.g. 10.0.22621.0
+const EWDK_INCLUDE      : &str = "Program Files/Windows Kits/10/Include";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Ewdk,
+    Tools,
+    Pycmd,
+}
+
+pub const KINDS         : [Kind; 3] = [Kind::Ewdk, Kind::Tools, Kind::Pycmd];
+
+impl Kind {
+    /// Key in aptio_v.toolkit of json
+    pub fn key(&self) -> &'static str {
+        match self {
+            Kind::Ewdk  => "ewdk",
+            Kind::Tools => "tools",
+            Kind::Pycmd => "pycmd",
+        }
+    }
+
+    /// Variable of the build environment, which is a candidate if it is set
+    fn env(&self) -> &'static str {
+        match self {
+            Kind::Ewdk  => "EWDK_DIR",
+            Kind::Tools => "TOOLS_DIR",
+            Kind::Pycmd => "PYTHON_COMMAND",
+        }
+    }
+
+    pub fn of<'a>(&self, toolkit: &'a audk::AptioToolkit) -> &'a Option<std::path::PathBuf> {
+        match self {
+            Kind::Ewdk  => &toolkit.ewdk,
+            Kind::Tools => &toolkit.tools,
+            Kind::Pycmd => &toolkit.pycmd,
+        }
+    }
+
+    pub fn pin<'a>(&self, pins: &'a audk::Pins) -> &'a Option<audk::Pin> {
+        match self {
+            Kind::Ewdk  => &pins.ewdk,
+            Kind::Tools => &pins.tools,
+            Kind::Pycmd => &pins.pycmd,
+        }
+    }
+
+    pub fn is_valid(&self, path: &std::path::Path) -> bool {
+        match self {
+            Kind::Ewdk  => is_ewdk(path),
+            Kind::Tools => is_tools(path),
+            Kind::Pycmd => is_pycmd(path),
+        }
+    }
+
+    pub fn version(&self, path: &std::path::Path) -> Option<String> {
+        match self {
+            Kind::Ewdk  => ewdk_version(path),
+            Kind::Tools => tools_version(path),
+            Kind::Pycmd => python_version(path),
+        }
+    }
+}
+
+/// A toolkit found, with its version if it is known
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub path            : std::path::PathBuf,
+    pub version         : Option<String>,
+}
+
+impl Candidate {
+    pub fn new(kind: Kind, path: std::path::PathBuf) -> Self {
+        let version = kind.version(&path);
+        Self { path, version }
+    }
+
+    pub fn name(&self) -> String {
+        match self.version.as_ref() {
+            Some(version) => format!("{} ({})", self.path.to_slash().unwrap(), version),
+            None => self.path.to_slash().unwrap(),
+        }
+    }
+}
+
+/// Toolkits found by discover, in the order of preference
+#[derive(Debug, Default)]
+pub struct Found {
+    pub ewdks           : Vec<Candidate>,
+    pub tools           : Vec<Candidate>,
+    pub pycmds          : Vec<Candidate>,
+}
+
+impl Found {
+    pub fn of(&self, kind: Kind) -> &[Candidate] {
+        match kind {
+            Kind::Ewdk  => &self.ewdks,
+            Kind::Tools => &self.tools,
+            Kind::Pycmd => &self.pycmds,
+        }
+    }
+}
+
+pub fn is_ewdk(dir: &std::path::Path) -> bool {
+    dir.join("LaunchBuildEnv.cmd").is_file()
+}
+
+pub fn is_tools(dir: &std::path::Path) -> bool {
+    dir.join("Bin").is_dir() && dir.join("make.exe").is_file()
+}
+
+pub fn is_pycmd(file: &std::path::Path) -> bool {
+    file.is_file() && file.file_name().map(|name| PYTHON_NAMES.iter().any(|n| name.to_string_lossy().eq_ignore_ascii_case(n))).unwrap_or(false)
+}
+
+/// First line of version.txt in the dir, in any case of its name
+fn version_file(dir: &std::path::Path) -> Option<String> {
+    let file = fs::read_dir(dir).ok()?.flatten().map(|e| e.path()).find(|p| {
+        p.is_file() && p.file_name().unwrap().to_string_lossy().eq_ignore_ascii_case("version.txt")
+    })?;
+    let text = String::from_utf8_lossy(&fs::read(file).ok()?).to_string();
+    text.lines().map(|line| line.trim()).find(|line| !line.is_empty()).map(String::from)
+}
+
+/// Version of EWDK, by version.txt or the latest SDK it has
+pub fn ewdk_version(dir: &std::path::Path) -> Option<String> {
+    version_file(dir).or_else(|| {
+        let mut sdks = fs::read_dir(dir.join(EWDK_INCLUDE)).ok()?.flatten()
+            .filter(|e| e.path().is_dir())
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .filter(|name| name.starts_with("10."))
+            .collect::<Vec<_>>();
+        sdks.sort_by_key(|sdk| sdk.split('.').map(|n| n.parse::<u32>().unwrap_or(0)).collect::<Vec<_>>());
+        sdks.pop()
+    })
+}
+
+/// Version of BuildTools, by version.txt of it or its Bin
+pub fn tools_version(dir: &std::path::Path) -> Option<String> {
+    version_file(dir).or_else(|| version_file(&dir.join("Bin")))
+}
+
+/// Version of python, by python --version, e.g. 3.8.10
+pub fn python_version(pycmd: &std::path::Path) -> Option<String> {
+    let output = std::process::Command::new(pycmd).arg("--version").output().ok()?;
+    // python 2 prints its version to stderr
+    let text = String::from_utf8_lossy(&output.stdout).to_string() + &String::from_utf8_lossy(&output.stderr);
+    text.split_whitespace().skip_while(|word| !word.eq_ignore_ascii_case("python")).nth(1).map(String::from)
+}
+
+/// Mismatches of the toolkit against its pin, empty if it matches
+pub fn verify(kind: Kind, path: &std::path::Path, pin: &audk::Pin) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    if let Some(want) = pin.version.as_ref() {
+        match kind.version(path) {
+            Some(version) if version == *want || version.starts_with(&(want.clone() + ".")) => {},
+            Some(version) => mismatches.push(format!("version is {}, want {}", version, want)),
+            None => mismatches.push(format!("version is unknown, want {}", want)),
+        }
+    }
+    let dir = if kind == Kind::Pycmd { path.parent().unwrap() } else { path };
+    for (file, want) in pin.sha256.iter().flatten() {
+        match sha256::file(&dir.join(file)) {
+            Ok(sha) if sha.eq_ignore_ascii_case(want) => {},
+            Ok(sha) => mismatches.push(format!("sha256 of {} is {}, want {}", file.to_slash().unwrap(), sha, want)),
+            Err(e) => mismatches.push(format!("sha256 of {} is unknown: {}", file.to_slash().unwrap(), e)),
+        }
+    }
+    mismatches
+}
+
+fn push(found: &mut Vec<std::path::PathBuf>, path: std::path::PathBuf) {
+    if !found.contains(&path) {
+        found.push(path);
+    }
+}
+
+/// Whether the dir is a toolkit, or has python in it, which is pushed to paths
+fn probe(dir: &std::path::Path, paths: &mut [Vec<std::path::PathBuf>; 3]) -> bool {
+    if is_ewdk(dir) {
+        push(&mut paths[0], dir.to_path_buf());
+        return true;
+    }
+    if is_tools(dir) {
+        push(&mut paths[1], dir.to_path_buf());
+        return true;
+    }
+    if let Some(pycmd) = PYTHON_NAMES.iter().map(|n| dir.join(n)).find(|p| is_pycmd(p)) {
+        push(&mut paths[2], pycmd);
+        return true;
+    }
+    false
+}
+
+/// Search toolkits in variables of the build environment, python on PATH, then dirs of roots,
+/// FWTO_TOOLKIT_ROOTS and common locations, and EWDK mounted at drives
+pub fn discover(roots: &Option<Vec<std::path::PathBuf>>) -> Found {
+    let mut paths = [Vec::new(), Vec::new(), Vec::new()];
+    for (i, kind) in KINDS.iter().enumerate() {
+        if let Some(path) = std::env::var_os(kind.env()).map(std::path::PathBuf::from) {
+            if kind.is_valid(&path) {
+                push(&mut paths[i], path);
+            }
+        }
+    }
+    for name in PYTHON_NAMES.iter() {
+        if let Some(pycmd) = hook::which(std::path::Path::new(name)) {
+            push(&mut paths[2], pycmd);
+        }
+    }
+
+    let mut dirs = roots.clone().unwrap_or_default();
+    if let Some(env) = std::env::var_os(TOOLKIT_ROOTS_ENV) {
+        dirs.extend(std::env::split_paths(&env));
+    }
+    dirs.extend(TOOLKIT_ROOTS.iter().map(std::path::PathBuf::from));
+    for root in dirs {
+        // walk visits children only, the root may be a toolkit itself
+        if !root.is_dir() || probe(&root, &mut paths) {
+            continue;
+        }
+        let depth = root.components().count();
+        ffs::walk(&root, &mut |path, is_dir| {
+            if !is_dir {
+                return false;
+            }
+            let name = path.file_name().unwrap().to_string_lossy();
+            if name.starts_with('$') || name.starts_with('.') || name == "Windows" || name == "System Volume Information" {
+                return false;
+            }
+            !probe(path, &mut paths) && path.components().count() - depth < TOOLKIT_DEPTH
+        });
+    }
+    for mount in TOOLKIT_MOUNTS.iter().map(std::path::Path::new) {
+        if is_ewdk(mount) {
+            push(&mut paths[0], mount.to_path_buf());
+        }
+    }
+    let [ewdks, tools, pycmds] = paths;
+    Found {
+        ewdks: ewdks.into_iter().map(|p| Candidate::new(Kind::Ewdk, p)).collect(),
+        tools: tools.into_iter().map(|p| Candidate::new(Kind::Tools, p)).collect(),
+        pycmds: pycmds.into_iter().map(|p| Candidate::new(Kind::Pycmd, p)).collect(),
+    }
+}
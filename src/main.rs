@@ -17,6 +17,11 @@ pub mod subcmd_ovrd;
 pub mod subcmd_cbup;
 pub mod subcmd_view;
 pub mod subcmd_build;
+pub mod subcmd_init;
+pub mod subcmd_doctor;
+pub mod subcmd_which;
+pub mod subcmd_bundle;
+pub mod subcmd_convert;
 
 #[derive(StructOpt, Debug)]
 /// AptioV Codebase Upgrade Toolkit @liuyi28@lenovo.com
@@ -53,43 +58,93 @@ enum Command {
     Build {
         #[structopt(flatten)]
         build       : audk::AptioV,
-        /// When --no-clean, build without clean
-        #[structopt(long)]
-        no_clean    : bool,
+        #[structopt(flatten)]
+        flags       : subcmd_build::BuildFlags,
+    },
+    /// Scaffold a configuration from an existing workspace
+    Init {
+        #[structopt(flatten)]
+        init        : subcmd_init::Init,
+    },
+    /// Diagnose the environment against current configuration
+    Doctor {
+        #[structopt(flatten)]
+        doctor      : subcmd_doctor::Doctor,
+    },
+    /// Show the effective source of a file across layers
+    Which {
+        #[structopt(flatten)]
+        which       : subcmd_which::Which,
+    },
+    /// Export overrides as a portable bundle
+    Export {
+        #[structopt(flatten)]
+        export      : subcmd_bundle::Export,
+    },
+    /// Import overrides of a bundle by three-way merge
+    Import {
+        #[structopt(flatten)]
+        import      : subcmd_bundle::Import,
+    },
+    /// Convert between overrides and in-place changes of codebase
+    Convert {
+        #[structopt(flatten)]
+        convert     : subcmd_convert::Convert,
     },
 }
 
 fn main() {
     let opt = Opts::from_args();
 
+    // init works without any configuration, even if it is broken
+    if let Some(Command::Init{init}) = opt.cmd.as_ref() {
+        return init.handler(&opt.project, &opt.audk_json);
+    }
     let audk_option = audk::Json::get(&opt.audk_json);
     if opt.cmd.is_none() {
         return
     }
 
-    let mut workspace   = std::path::PathBuf::new();
-    let mut cfg_aptio_v = None;
-    let mut cfg_oemovrd = None;
-    let mut cfg_ibvovrd = None;
+    let mut workspace     = std::path::PathBuf::new();
+    let mut cfg_aptio_v   = None;
+    let mut cfg_layers    = Vec::new();
+    let mut cfg_explicit  = false;
+    let mut cfg_difftool  = None;
+    let mut cfg_mergetool = None;
+    let mut cfg_renames   = None;
+    let mut cfg_build     = None;
     if let Some(audk) = audk_option {
-        workspace   = audk.project.workspace.unwrap();
-        cfg_aptio_v = audk.aptio_v;
-        cfg_oemovrd = audk.oemovrd;
-        cfg_ibvovrd = audk.ibvovrd;
+        cfg_layers    = audk.layers();
+        cfg_explicit  = audk.layers.is_some();
+        workspace     = audk.project.workspace.unwrap();
+        cfg_aptio_v   = audk.aptio_v;
+        cfg_difftool  = audk.difftool;
+        cfg_mergetool = audk.mergetool;
+        cfg_renames   = audk.renames;
+        cfg_build     = audk.build;
     }
 
     if let Some(ws) = opt.project.workspace {
         workspace = ws;
     }
+    let layers = audk::Layers::new(cfg_layers, cfg_explicit, &opt.oemovrd);
+    // doctor works even if workspace is invalid
+    if let Some(Command::Doctor{doctor}) = opt.cmd.as_ref() {
+        return doctor.handler(&workspace, &layers, &cfg_aptio_v.as_ref());
+    }
     let wp = workspace.parent().unwrap();
     let ws =&workspace;
     if !ws.join("MdePkg").is_dir() && ws.is_dir() {
         std::env::set_current_dir(ws).unwrap();
-        setup_workspace(&cfg_aptio_v.as_ref());
+        if !setup_workspace(&cfg_aptio_v.as_ref()) {
+            return
+        }
     }
     if !ws.join("MdePkg").is_dir() && wp.is_dir() {
         std::env::set_current_dir(wp).unwrap();
-        setup_workspace(&cfg_aptio_v.as_ref()); 
+        if !setup_workspace(&cfg_aptio_v.as_ref()) {
+            return
+        }
     }
     if !ws.join("MdePkg").is_dir() {
         println!("ERR: invalid workspace {:?}", ws);
@@ -97,23 +152,37 @@ fn main() {
     }
     std::env::set_current_dir(ws).unwrap();
 
+    let cfg_scripts = cfg_aptio_v.as_ref().and_then(|aptio_v| aptio_v.scripts.as_deref());
     match opt.cmd.as_ref().unwrap() {
         Command::Ovrd{ovrd} => {
-            ovrd.handler(&opt.oemovrd, &cfg_oemovrd.as_ref(), &cfg_ibvovrd.as_ref());
+            ovrd.handler(&layers, &cfg_scripts);
         },
         Command::Cbup{diff} => {
-            diff.handler(&opt.oemovrd, &cfg_oemovrd.as_ref(), &cfg_ibvovrd.as_ref());
+            diff.handler(&layers, &cfg_mergetool.as_ref(), &cfg_renames.as_ref(), &cfg_scripts);
         },
         Command::View{diff} => {
-            diff.handler(&opt.oemovrd, &cfg_oemovrd.as_ref(), &cfg_ibvovrd.as_ref());
+            diff.handler(&layers, &cfg_difftool.as_ref(), &cfg_scripts);
         },
-        Command::Build{build, no_clean} => {
-            build.handler(&cfg_aptio_v.as_ref(), *no_clean);
+        Command::Build{build, flags} => {
+            build.handler(&cfg_aptio_v.as_ref(), &cfg_build.as_ref(), flags);
         },
+        Command::Which{which} => {
+            which.handler(&layers);
+        },
+        Command::Export{export} => {
+            export.handler(&layers);
+        },
+        Command::Import{import} => {
+            import.handler(&layers);
+        },
+        Command::Convert{convert} => {
+            convert.handler(&layers);
+        },
+        Command::Init{..} | Command::Doctor{..} => {},
     }
 }
 
-fn setup_workspace(cfg_aptio_v: &Option<&crate::audk::AptioV>) {
+fn setup_workspace(cfg_aptio_v: &Option<&crate::audk::AptioV>) -> bool {
     //
     // scripts of build hooks
     //
@@ -129,17 +198,11 @@ fn setup_workspace(cfg_aptio_v: &Option<&crate::audk::AptioV>) {
     //
     // setup workspace hook
     //
+    let mut reports = Vec::new();
+    let mut result = true;
     if let Some(scripts) = scripts {
-        if let Some(work_space) = scripts.work_space.as_ref() {
-            for i in work_space {
-                let null_args = Vec::<String>::new();
-                let null_farg =       String ::new();
-                let args = if let Some(args) = i.args.as_ref() { args } else { &null_args };
-                let farg = if let Some(farg) = i.farg.as_ref() { farg } else { &null_farg };
-                if i.file.is_file() {
-                    std::process::Command::new(&i.interpreter).args(args).arg(farg).arg(&i.file).status().unwrap();
-                }
-            }
-        }
+        result = libs::hook::run(&scripts.work_space, &libs::hook::Context::new("work_space"), &mut reports);
     }
+    libs::hook::summary(&reports);
+    result
 }
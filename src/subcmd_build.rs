@@ -9,9 +9,120 @@
   WITHOUT WARRANTIES OR REPRESENTATIONS OF ANY KIND, EITHER EXPRESS OR IMPLIED.
 --*/
 
+use std::fs;
+use std::io::prelude::*;
+use std::io::BufReader;
+use structopt::StructOpt;
+
+use crate::audk;
 use crate::audk::AptioV as Build;
 use crate::audk::AptioProject;
 use crate::audk::AptioToolkit;
+use crate::libs::ffs;
+use crate::libs::git;
+use crate::libs::history;
+use crate::libs::hook;
+use crate::libs::sha256;
+use crate::libs::toolkit;
+use path_slash::PathBufExt;
+
+const BUILD_HOME        : &str = "build";
+const BUILD_LOG         : &str = "build.log";
+const BUILD_ARTIFACTS   : &str = "artifacts";
+const BUILD_HISTORY     : &str = "history.jsonl";
+const ARTIFACTS_NAME    : &str = "{veb}_{branch}_{shortsha}_{date}";
+
+/// Build record of the artifacts, written beside them
+#[derive(serde::Serialize)]
+struct Manifest {
+    commit              : Option<String>,
+    branch              : Option<String>,
+    dirty               : bool,
+    date                : String,
+    veb                 : std::path::PathBuf,
+    toolkit             : std::collections::BTreeMap<String, std::path::PathBuf>,
+    artifacts           : Vec<Artifact>,
+}
+
+#[derive(serde::Serialize)]
+struct Artifact {
+    /// Name in the output dir
+    file                : String,
+    /// Where it is built, relative to workspace
+    source              : String,
+    size                : u64,
+    sha256              : String,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct BuildFlags {
+    /// When --no-clean, build without clean
+    #[structopt(long)]
+    pub no_clean        : bool,
+    /// Goal of make instead of rebuild, e.g. a module, which wins over goals of the preset
+    #[structopt(long = "target", value_name = "goal")]
+    pub targets         : Vec<String>,
+    /// Variable of make as TOKEN=VALUE, which wins over the preset
+    #[structopt(short = "D", long = "define", value_name = "TOKEN=VALUE", parse(try_from_str = parse_define))]
+    pub defines         : Vec<(String, String)>,
+    /// Preset of goals and variables, in build.presets of json
+    #[structopt(long)]
+    pub preset          : Option<String>,
+    /// Build every variant of build.matrix in json, instead of the project veb
+    #[structopt(long, conflicts_with = "veb")]
+    pub matrix          : bool,
+    /// Print make time of recent builds, and the slowest modules against the previous build
+    #[structopt(long)]
+    pub history         : bool,
+    /// Print environment of make instead of build, as json, or a script of sh, cmd or ps1
+    #[structopt(long, value_name = "format", possible_values = &["json", "sh", "cmd", "ps1"], conflicts_with = "matrix")]
+    pub print_env       : Option<String>,
+    /// Build even if toolkits mismatch build.pins in json
+    #[structopt(long)]
+    pub ignore_pins     : bool,
+    /// Arguments passed to make as they are, e.g. -- clean
+    #[structopt(last = true)]
+    pub passthrough     : Vec<String>,
+}
+
+fn parse_define(define: &str) -> Result<(String, String), String> {
+    match define.split_once('=') {
+        Some((token, value)) if !token.trim().is_empty() => Ok((token.trim().to_string(), value.to_string())),
+        _ => Err(format!("want TOKEN=VALUE, but {:?}", define)),
+    }
+}
+
+impl BuildFlags {
+    /// Arguments of make: goals, variables, then passthrough ones
+    fn make_args(&self, cfg_build: &Option<&audk::BuildConfig>, preset: &Option<String>) -> Result<Vec<String>, String> {
+        let preset = match preset.as_ref() {
+            Some(name) => match cfg_build.and_then(|cfg_build| cfg_build.presets.as_ref()).and_then(|presets| presets.get(name)) {
+                Some(preset) => Some(preset),
+                None => return Err(format!("preset {:?} is not in build.presets of json", name)),
+            },
+            None => None,
+        };
+        let mut args = if !self.targets.is_empty() {
+            self.targets.clone()
+        } else if let Some(targets) = preset.and_then(|preset| preset.targets.clone()) {
+            targets
+        } else if !self.passthrough.is_empty() {
+            // goals are given after --
+            Vec::new()
+        } else if self.no_clean {
+            vec![String::from("all")]
+        } else {
+            vec![String::from("rebuild")]
+        };
+        let mut defines = preset.and_then(|preset| preset.defines.clone()).unwrap_or_default();
+        for (token, value) in &self.defines {
+            defines.insert(token.clone(), value.clone());
+        }
+        args.extend(defines.iter().map(|(token, value)| format!("{}={}", token, value)));
+        args.extend(self.passthrough.iter().cloned());
+        Ok(args)
+    }
+}
 
 impl Build {
     pub fn new() -> Self {
@@ -19,8 +130,63 @@ impl Build {
             project: AptioProject { veb: None }, toolkit: AptioToolkit { ewdk: None, tools: None, pycmd: None }, scripts: None
         }
     }
-    pub fn handler(&self, cfg_aptio_v: &Option<&Build>, no_clean: bool) {
-        let cmd: (&str, &str) = if cfg!(target_os = "windows") { ("powershell", "-command") } else { ("sh", "-c") };
+    pub fn handler(&self, cfg_aptio_v: &Option<&Build>, cfg_build: &Option<&audk::BuildConfig>, flags: &BuildFlags) {
+        if flags.history {
+            return history::print(&std::path::PathBuf::from(audk::FWTO_WS).join(BUILD_HOME).join(BUILD_HISTORY));
+        }
+        if flags.matrix {
+            return self.build_matrix(cfg_aptio_v, cfg_build, flags);
+        }
+        let variant = audk::Variant { name: None, veb: None, preset: flags.preset.clone() };
+        self.build_variant(cfg_aptio_v, cfg_build, flags, &variant);
+    }
+
+    /// Build every variant of build.matrix in turn
+    fn build_matrix(&self, cfg_aptio_v: &Option<&Build>, cfg_build: &Option<&audk::BuildConfig>, flags: &BuildFlags) {
+        let matrix = match cfg_build.and_then(|cfg_build| cfg_build.matrix.as_ref()) {
+            Some(matrix) if !matrix.is_empty() => matrix,
+            _ => {
+                println!("ERR: build.matrix is not in json");
+                return
+            }
+        };
+        let mut results = Vec::new();
+        for variant in matrix {
+            // preset of cmdline is for variants without their own
+            let variant = audk::Variant { preset: variant.preset.clone().or_else(|| flags.preset.clone()), ..variant.clone() };
+            let name = variant.name();
+            println!("---------------------------");
+            println!("INF: build variant {}", name);
+            println!("---------------------------");
+            let start = std::time::Instant::now();
+            let status = self.build_variant(cfg_aptio_v, cfg_build, flags, &audk::Variant { name: Some(name.clone()), ..variant });
+            results.push((name, status, start.elapsed()));
+        }
+        println!("---------------------------");
+        println!("INF: build matrix");
+        for (name, status, elapsed) in &results {
+            let result = match status {
+                Some(0)    => String::from("passed"),
+                Some(code) => format!("failed({})", code),
+                None       => String::from("error"),
+            };
+            println!("  {:<12} {:>7.1}s  {}", result, elapsed.as_secs_f64(), name);
+        }
+        let passed = results.iter().filter(|(_, status, _)| *status == Some(0)).count();
+        println!("INF: {} passed, {} failed", passed, results.len() - passed);
+    }
+
+    /// Build the variant, with exit code of make, or None if it is not built or terminated
+    fn build_variant(&self, cfg_aptio_v: &Option<&Build>, cfg_build: &Option<&audk::BuildConfig>, flags: &BuildFlags, variant: &audk::Variant) -> Option<i32> {
+        let start = std::time::Instant::now();
+        let date  = utc_datetime();
+        let make_args = match flags.make_args(cfg_build, &variant.preset) {
+            Ok(make_args) => make_args,
+            Err(e) => {
+                println!("ERR: {}", e);
+                return None
+            }
+        };
         // scripts of build hooks
         let scripts = if let Some(cfg_aptio_v) = cfg_aptio_v {
             if let Some(scripts) = cfg_aptio_v.scripts.as_ref() {
@@ -31,94 +197,305 @@ impl Build {
         } else {
             None
         };
+        let mut reports = Vec::new();
+        let mut context = hook::Context::new("fore_build");
+        // veb as it is configured, it may be made by hooks, and the build environment is not known yet
+        context.veb        = variant.veb.as_ref().or(self.project.veb.as_ref())
+            .or_else(|| cfg_aptio_v.and_then(|cfg_aptio_v| cfg_aptio_v.project.veb.as_ref())).cloned();
+        context.build_mode = Some(make_args.join(" "));
         //
-        // fore_build hooks
+        // fore_build hooks, before veb and toolkits are validated, as they may prepare them
         //
         if let Some(scripts) = scripts {
-            if let Some(fore_build) = scripts.fore_build.as_ref() {
-                for i in fore_build {
-                    let null_args = Vec::<String>::new();
-                    let null_farg =       String ::new();
-                    let args = if let Some(args) = i.args.as_ref() { args } else { &null_args };
-                    let farg = if let Some(farg) = i.farg.as_ref() { farg } else { &null_farg };
-                    if i.file.is_file() {
-                        std::process::Command::new(&i.interpreter).args(args).arg(farg).arg(&i.file).status().unwrap();
-                    }
-                }
+            if flags.print_env.is_none() && !hook::run(&scripts.fore_build, &context, &mut reports) {
+                hook::summary(&reports);
+                return None
             }
         }
-
-        let veb = if let Some(veb) = &self.project.veb {
+        let veb = if let Some(veb) = variant.veb.as_ref().or(self.project.veb.as_ref()) {
             veb
         } else {
             cfg_aptio_v.expect("ERR: aptio_v is neither given in cmdline or json").project.veb.as_ref().expect("ERR: veb is None in json.")
         };
         if !veb.is_file() {
             println!("ERR: invalid project veb: {:?}", veb);
-            return
+            return None
         }
 
-        let ewdk = if let Some(ewdk) = &self.toolkit.ewdk {
-            ewdk
-        } else {
-            cfg_aptio_v.expect("ERR: aptio_v is neither given in cmdline or json").toolkit.ewdk.as_ref().expect("ERR: ewdk is None in json.")
-        };
-        if !ewdk.join("LaunchBuildEnv.cmd").is_file() {
-            println!("ERR: invalid ewdk {:?}", ewdk);
-            return
+        // toolkit of cmdline, or json, or the first one discovered
+        let mut found = None;
+        let mut paths = Vec::new();
+        for kind in toolkit::KINDS {
+            let path = match kind.of(&self.toolkit).as_ref().or_else(|| cfg_aptio_v.and_then(|cfg_aptio_v| kind.of(&cfg_aptio_v.toolkit).as_ref())) {
+                Some(path) => path.clone(),
+                None => {
+                    let found = found.get_or_insert_with(|| toolkit::discover(&cfg_build.and_then(|cfg_build| cfg_build.toolkit_roots.clone())));
+                    // the one matching its pin is preferred
+                    let pin = cfg_build.and_then(|cfg_build| cfg_build.pins.as_ref()).and_then(|pins| kind.pin(pins).as_ref());
+                    let matched = pin.and_then(|pin| found.of(kind).iter().find(|c| toolkit::verify(kind, &c.path, pin).is_empty()));
+                    match matched.or_else(|| found.of(kind).first()) {
+                        Some(candidate) => {
+                            println!("WRN: {} is neither given in cmdline or json, discovered {}, fill json by: fwto init --toolkit", kind.key(), candidate.name());
+                            candidate.path.clone()
+                        },
+                        None => {
+                            println!("ERR: {} is neither given in cmdline or json, nor discovered", kind.key());
+                            return None
+                        }
+                    }
+                }
+            };
+            if !kind.is_valid(&path) {
+                println!("ERR: invalid {} {:?}", kind.key(), path);
+                return None
+            }
+            paths.push(path);
         }
-        let tools = if let Some(tools) = &self.toolkit.tools {
-            tools
-        } else {
-            cfg_aptio_v.expect("ERR: aptio_v is neither given in cmdline or json").toolkit.tools.as_ref().expect("ERR: tools is None in json.")
-        };
-        if !tools.join("Bin").is_dir() || !tools.join("make.exe").is_file() {
-            println!("ERR: invalid tools {:?}", tools);
-            return
+        let (ewdk, tools, pycmd) = (&paths[0], &paths[1], &paths[2]);
+        if let Some(pins) = cfg_build.and_then(|cfg_build| cfg_build.pins.as_ref()) {
+            if !verify_pins(pins, &paths, flags.ignore_pins) {
+                return None
+            }
         }
-        let pycmd = if let Some(pycmd) = &self.toolkit.pycmd {
-            pycmd
-        } else {
-            cfg_aptio_v.expect("ERR: aptio_v is neither given in cmdline or json").toolkit.pycmd.as_ref().expect("ERR: pycmd is None in json.")
-        };
-        if (!pycmd.is_file()) || (pycmd.file_name().unwrap() != "python.exe") {
-            println!("ERR: invalid pycmd {:?}", pycmd);
-            return
-        }
-
-        std::env::set_var(            "VEB" , veb.file_stem().unwrap()  );
-        std::env::set_var(       "EWDK_DIR" , &ewdk                     );
-        std::env::set_var(      "TOOLS_DIR" , &tools                    );
-        std::env::set_var( "PYTHON_COMMAND" , &pycmd                    );
-        let pyth = String::new() + pycmd.parent().unwrap().to_str().unwrap() + ";" + pycmd.parent().unwrap().join("Scripts").to_str().unwrap() + ";";
-        let path = if let Some(path) = std::env::var_os("PATH") {
-            String::new() + &pyth + tools.to_str().unwrap() + ";" + path.to_str().unwrap()
-        } else {
-            String::new() + &pyth + tools.to_str().unwrap()
-        };
-        std::env::set_var(           "PATH" , path                      );
 
-        let make_opts = if no_clean {
-            String::from("all")
-        } else {
-            String::from("rebuild")
+        // environment of make and hooks only, fwto itself is as it is started
+        let env = match build_env(veb, ewdk, tools, pycmd) {
+            Ok(env) => env,
+            Err(e) => {
+                println!("ERR: invalid PATH of build: {}", e);
+                return None
+            }
         };
-        std::process::Command::new(cmd.0).arg(cmd.1).arg("make").arg(make_opts).status().unwrap();
+        if let Some(format) = flags.print_env.as_ref() {
+            print_env(&env, format);
+            return None
+        }
+
+        // logs and artifacts of variants are in their own dirs
+        let root = std::path::PathBuf::from(audk::FWTO_WS).join(BUILD_HOME);
+        let home = variant.name.as_ref().map(|name| root.join(name)).unwrap_or_else(|| root.clone());
+        fs::create_dir_all(&home).unwrap();
+        let log = std::env::current_dir().unwrap().join(home).join(BUILD_LOG);
+        let make_start = std::time::Instant::now();
+        let (status, modules) = make(&make_args, &env, &log);
+        let make_secs = make_start.elapsed().as_secs_f64();
+        match status {
+            Some(0) => println!("INF: build passed, log is in {:?}", log),
+            Some(code) => println!("ERR: build failed with exit code {}, log is in {:?}", code, log),
+            None => println!("ERR: build is terminated, log is in {:?}", log),
+        }
+        if let Some(artifacts) = cfg_build.and_then(|cfg_build| cfg_build.artifacts.as_ref()) {
+            if status == Some(0) {
+                collect(artifacts, veb, &variant.name, &[("ewdk", ewdk), ("tools", tools), ("pycmd", pycmd)]);
+            }
+        }
         //
         // post_build hooks
         //
         if let Some(scripts) = scripts {
-            if let Some(post_build) = scripts.post_build.as_ref() {
-                for i in post_build {
-                    let null_args = Vec::<String>::new();
-                    let null_farg =       String ::new();
-                    let args = if let Some(args) = i.args.as_ref() { args } else { &null_args };
-                    let farg = if let Some(farg) = i.farg.as_ref() { farg } else { &null_farg };
-                    if i.file.is_file() {
-                        std::process::Command::new(&i.interpreter).args(args).arg(farg).arg(&i.file).status().unwrap();
-                    }
+            context.stage        = String::from("post_build");
+            context.veb          = Some(veb.clone());
+            context.env          = env.clone();
+            context.build_status = status;
+            context.build_log    = Some(log);
+            hook::run(&scripts.post_build, &context, &mut reports);
+        }
+        hook::summary(&reports);
+        history::append(&root.join(BUILD_HISTORY), &history::Record {
+            date,
+            commit: git::rev_parse("HEAD"),
+            branch: git::branch(),
+            variant: variant.name.clone().unwrap_or_else(|| veb.file_stem().unwrap().to_string_lossy().to_string()),
+            make_args,
+            status,
+            total: start.elapsed().as_secs_f64(),
+            make: make_secs,
+            hooks: reports.iter().map(|r| history::Timing { name: format!("{}: {}", r.stage, r.hook), secs: r.elapsed.as_secs_f64() }).collect(),
+            modules,
+        });
+        status
+    }
+}
+
+/// Verify toolkits against pins, false if one mismatches and it is not ignored
+fn verify_pins(pins: &audk::Pins, paths: &[std::path::PathBuf], ignore: bool) -> bool {
+    let mut matched = true;
+    for (kind, path) in toolkit::KINDS.iter().zip(paths) {
+        let pin = match kind.pin(pins) {
+            Some(pin) => pin,
+            None => continue,
+        };
+        let mismatches = toolkit::verify(*kind, path, pin);
+        if mismatches.is_empty() {
+            println!("INF: {} matches pin: {:?}", kind.key(), path);
+            continue;
+        }
+        for mismatch in mismatches {
+            println!("{}: {} {:?} mismatches pin: {}", if ignore { "WRN" } else { "ERR" }, kind.key(), path, mismatch);
+        }
+        matched = false;
+    }
+    if !matched && !ignore {
+        println!("ERR: build is refused, switch the toolkit, or fix build.pins in json, or build with --ignore-pins");
+    }
+    matched || ignore
+}
+
+/// Variables of the build, set over environment of fwto for make and hooks
+fn build_env(veb: &std::path::Path, ewdk: &std::path::Path, tools: &std::path::Path, pycmd: &std::path::Path) -> Result<Vec<(String, std::ffi::OsString)>, std::env::JoinPathsError> {
+    let pydir = pycmd.parent().unwrap();
+    let mut paths = vec![pydir.to_path_buf(), pydir.join("Scripts"), tools.to_path_buf()];
+    if let Some(path) = std::env::var_os("PATH") {
+        paths.extend(std::env::split_paths(&path));
+    }
+    Ok(vec![
+        (String::from(           "VEB"), veb.file_stem().unwrap().to_os_string()),
+        (String::from(      "EWDK_DIR"), ewdk.as_os_str().to_os_string()),
+        (String::from(     "TOOLS_DIR"), tools.as_os_str().to_os_string()),
+        (String::from("PYTHON_COMMAND"), pycmd.as_os_str().to_os_string()),
+        (String::from(          "PATH"), std::env::join_paths(paths)?),
+    ])
+}
+
+/// Print the whole environment of make, to reproduce the build out of fwto
+fn print_env(env: &[(String, std::ffi::OsString)], format: &str) {
+    // names of variables are case-insensitive on windows
+    let same = |a: &str, b: &str| if cfg!(target_os = "windows") { a.eq_ignore_ascii_case(b) } else { a == b };
+    let mut vars = std::env::vars_os()
+        .map(|(key, value)| (key.to_string_lossy().to_string(), value.to_string_lossy().to_string()))
+        .filter(|(key, _)| !env.iter().any(|(k, _)| same(k, key)))
+        .collect::<std::collections::BTreeMap<_, _>>();
+    vars.extend(env.iter().map(|(key, value)| (key.clone(), value.to_string_lossy().to_string())));
+    match format {
+        "json" => println!("{}", serde_json::to_string_pretty(&vars).unwrap()),
+        "sh"   => vars.iter().for_each(|(key, value)| println!("export {}='{}'", key, value.replace('\'', r#"'\''"#))),
+        "cmd"  => vars.iter().for_each(|(key, value)| println!("set \"{}={}\"", key, value)),
+        _      => vars.iter().for_each(|(key, value)| println!("${{env:{}}} = '{}'", key, value.replace('\'', "''"))),
+    }
+}
+
+/// Run make, and tee its stdout and stderr to the log, with timings of modules built
+fn make(make_args: &[String], env: &[(String, std::ffi::OsString)], log: &std::path::Path) -> (Option<i32>, Vec<history::Timing>) {
+    let flog = std::sync::Arc::new(std::sync::Mutex::new(fs::File::create(log).unwrap()));
+    let starts = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let start = std::time::Instant::now();
+    // make is spawned with its arguments as they are, no shell is between to split or expand them,
+    // and it is found on PATH of the build
+    let makecmd = make_args.iter().fold(String::from("make"), |makecmd, arg| {
+        if arg.contains(' ') { makecmd + " " + r#"""# + arg + r#"""# } else { makecmd + " " + arg }
+    });
+    println!("INF: {}", makecmd);
+    let child = std::process::Command::new("make").args(make_args).envs(env.iter().map(|(key, value)| (key, value)))
+        .stdout(std::process::Stdio::piped()).stderr(std::process::Stdio::piped()).spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            println!("ERR: failed to run make: {}", e);
+            writeln!(flog.lock().unwrap(), "failed to run make: {}", e).unwrap();
+            return (None, Vec::new())
+        }
+    };
+    let tee = |pipe: Box<dyn Read + Send>, is_err: bool| {
+        let flog = flog.clone();
+        let starts = starts.clone();
+        std::thread::spawn(move || {
+            for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+                // a module is started once its line is out
+                if let Some(module) = history::module_of(&line) {
+                    starts.lock().unwrap().push(history::Timing { name: module, secs: start.elapsed().as_secs_f64() });
                 }
+                if is_err { eprintln!("{}", line) } else { println!("{}", line) }
+                writeln!(flog.lock().unwrap(), "{}", line).unwrap();
             }
+        })
+    };
+    let stdout = tee(Box::new(child.stdout.take().unwrap()), false);
+    let stderr = tee(Box::new(child.stderr.take().unwrap()), true);
+    stdout.join().unwrap();
+    stderr.join().unwrap();
+    let status = child.wait().unwrap().code();
+    let mut starts = starts.lock().unwrap().clone();
+    starts.sort_by(|a, b| a.secs.partial_cmp(&b.secs).unwrap_or(std::cmp::Ordering::Equal));
+    (status, history::modules(&starts, start.elapsed().as_secs_f64()))
+}
+
+/// Date of today in UTC, as yyyymmdd
+fn utc_date() -> String {
+    utc_datetime()[..8].to_string()
+}
+
+/// Date and time of now in UTC, as yyyymmdd-hhmmss
+fn utc_datetime() -> String {
+    let secs = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs() as i64;
+    let time = secs.rem_euclid(86400);
+    // days to civil date, by Howard Hinnant
+    let z = secs / 86400 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    format!("{:04}{:02}{:02}-{:02}{:02}{:02}", y, m, d, time / 3600, time % 3600 / 60, time % 60)
+}
+
+/// Copy files matched by patterns to the output dir with names of the template, and write the manifest
+fn collect(artifacts: &audk::Artifacts, veb: &std::path::Path, variant: &Option<String>, toolkit: &[(&str, &std::path::PathBuf)]) {
+    let mut files = artifacts.patterns.iter().flat_map(|pattern| ffs::glob(pattern)).collect::<Vec<_>>();
+    files.sort();
+    files.dedup();
+    if files.is_empty() {
+        println!("WRN: no artifact is matched by {:?}", artifacts.patterns);
+        return;
+    }
+    let commit = git::rev_parse("HEAD");
+    let branch = git::branch();
+    let date   = utc_date();
+    let mut output = artifacts.output.clone().unwrap_or_else(|| std::path::PathBuf::from(audk::FWTO_WS).join(BUILD_HOME).join(BUILD_ARTIFACTS));
+    if let Some(variant) = variant {
+        output = output.join(variant);
+    }
+    let mut template = artifacts.name.clone().unwrap_or_else(|| String::from(ARTIFACTS_NAME));
+    // names of artifacts are unique by their own names
+    if files.len() > 1 && !template.contains("{name}") {
+        template += "_{name}";
+    }
+    let stem = |name: &str| template
+        .replace("{veb}", &veb.file_stem().unwrap().to_string_lossy())
+        .replace("{branch}", &branch.clone().unwrap_or_else(|| String::from("detached")).replace(['/', '\\'], "-"))
+        .replace("{shortsha}", commit.as_ref().map(|c| &c[..c.len().min(8)]).unwrap_or("unknown"))
+        .replace("{date}", &date)
+        .replace("{name}", name);
+    fs::create_dir_all(&output).unwrap();
+
+    // paths resolved to absolute, as the manifest may be read anywhere
+    let ws = std::env::current_dir().unwrap();
+    let mut manifest = Manifest {
+        commit: commit.clone(),
+        branch: branch.clone(),
+        dirty: git::status_porcelain().map(|status| !status.is_empty()).unwrap_or(false),
+        date: date.clone(),
+        veb: ws.join(veb),
+        toolkit: toolkit.iter().map(|(name, path)| (String::from(*name), ws.join(path))).collect(),
+        artifacts: Vec::new(),
+    };
+    for file in &files {
+        let mut name = stem(&file.file_stem().unwrap().to_string_lossy());
+        if let Some(ext) = file.extension() {
+            name = name + "." + &ext.to_string_lossy();
         }
+        ffs::copy(file, output.join(&name)).unwrap();
+        println!("INF: artifact {:?} -> {}", file, name);
+        manifest.artifacts.push(Artifact {
+            file: name,
+            source: file.to_slash().unwrap(),
+            size: fs::metadata(file).unwrap().len(),
+            sha256: sha256::file(file).unwrap(),
+        });
     }
+    let fmanifest = output.join(stem("manifest") + ".json");
+    fs::write(&fmanifest, serde_json::to_string_pretty(&manifest).unwrap() + "\n").unwrap();
+    println!("INF: {} artifacts are collected, manifest is written: {:?}", manifest.artifacts.len(), fmanifest);
 }
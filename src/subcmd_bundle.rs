@@ -0,0 +1,251 @@
+/*++ @file
+
+  Copyright ©2021 Liu Yi, liuyi28@lenovo.com
+
+  This program is just made available under the terms and conditions of the
+  MIT license: http://www.efikarl.com/mit-license.html
+
+  THE PROGRAM IS DISTRIBUTED UNDER THE MIT LICENSE ON AN "AS IS" BASIS,
+  WITHOUT WARRANTIES OR REPRESENTATIONS OF ANY KIND, EITHER EXPRESS OR IMPLIED.
+--*/
+
+use std::fs;
+use structopt::StructOpt;
+use serde::{Serialize, Deserialize};
+use path_slash::PathBufExt;
+
+use crate::audk;
+use crate::subcmd_ovrd;
+use crate::libs::cif;
+use crate::libs::ffs;
+use crate::libs::git;
+
+#[derive(StructOpt, Debug)]
+pub struct Export {
+    /// Dir of the bundle to be written
+    #[structopt(short, long, parse(from_os_str))]
+    pub output          : std::path::PathBuf,
+    /// Only export overrides of these files or dirs, relative to workspace
+    #[structopt(parse(from_os_str))]
+    pub paths           : Vec<std::path::PathBuf>,
+}
+
+#[derive(StructOpt, Debug)]
+pub struct Import {
+    /// Dir of the bundle written by export
+    #[structopt(parse(from_os_str))]
+    pub bundle          : std::path::PathBuf,
+}
+
+const BUNDLE_JSON       : &str = "fwto.bundle.json";
+const BUNDLE_ORG        : &str = "org";
+const BUNDLE_OVRD       : &str = "ovrd";
+const IMPORT_HOME       : &str = "import";
+const IMPORT_OLD_       : &str = "base.old";
+const IMPORT_NEW_       : &str = "base.new";
+const IMPORT_MERGED     : &str = "merged";
+
+/// Metadata of the bundle, files of it are in org and ovrd
+#[derive(Serialize, Deserialize, Debug)]
+struct Bundle {
+    /// Commit of workspace where the bundle is exported
+    commit              : Option<String>,
+    /// Layer of overrides in the bundle
+    layer               : String,
+    files               : Vec<BundleFile>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct BundleFile {
+    /// Overridden file, relative to workspace in slash
+    file                : String,
+    /// Override statement in cif, as it is
+    cif_line            : String,
+    /// Whether original is in org, or it is an override-only file
+    org                 : bool,
+}
+
+/// Result of importing a file, in the order of report
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Applied,
+    Unchanged,
+    Conflict,
+    Overridden,
+    Missing,
+}
+
+impl Outcome {
+    fn name(&self) -> &'static str {
+        match self {
+            Outcome::Applied    => "applied",
+            Outcome::Unchanged  => "unchanged",
+            Outcome::Conflict   => "conflict",
+            Outcome::Overridden => "overridden",
+            Outcome::Missing    => "missing",
+        }
+    }
+}
+
+fn is_under(file: &std::path::PathBuf, paths: &[std::path::PathBuf]) -> bool {
+    let file = file.to_slash().unwrap().to_ascii_lowercase();
+    paths.is_empty() || paths.iter().any(|path| {
+        let path = path.to_slash().unwrap().replace('\\', "/").trim_end_matches('/').to_ascii_lowercase();
+        file == path || file.starts_with(&(path + "/"))
+    })
+}
+
+impl Export {
+    pub fn handler(&self, layers: &audk::Layers) {
+        let top = layers.top().expect("ERR: oemovrd is neither given in cmdline or json");
+        let cif = top.cif.as_ref().expect("ERR: cif is None in json.");
+        if !cif.is_file() {
+            println!("ERR: want override.cif, but not a file: {:?}", cif);
+            return;
+        }
+        if self.output.join(BUNDLE_JSON).is_file() {
+            println!("ERR: bundle is there, remove it or export to another dir: {:?}", self.output);
+            return;
+        }
+        let lines = String::from_utf8_lossy(&fs::read(cif).unwrap()).to_string();
+
+        let mut bundle = Bundle { commit: git::rev_parse("HEAD"), layer: top.name.clone(), files: Vec::new() };
+        for line in lines.lines() {
+            let entry = match cif::parse_line(line) {
+                Some(entry) => entry,
+                None => continue,
+            };
+            if !is_under(&entry.src, &self.paths) {
+                continue;
+            }
+            let fdst = cif.parent().unwrap().join(&entry.dst);
+            if !fdst.is_file() {
+                println!("WRN: override is not found, skip it: {:?}", fdst);
+                continue;
+            }
+            // original is the one in org, or codebase as it is not overridden in place
+            let forg = match top.org.as_ref().map(|org| org.join(&entry.src)) {
+                Some(forg) if forg.is_file() => Some(forg),
+                _ if entry.src.is_file() => Some(entry.src.clone()),
+                _ => None,
+            };
+            let ovrd = self.output.join(BUNDLE_OVRD).join(&entry.src);
+            fs::create_dir_all(ovrd.parent().unwrap()).unwrap();
+            ffs::copy(&fdst, &ovrd).unwrap();
+            if let Some(forg) = forg.as_ref() {
+                let org = self.output.join(BUNDLE_ORG).join(&entry.src);
+                fs::create_dir_all(org.parent().unwrap()).unwrap();
+                ffs::copy(forg, &org).unwrap();
+            }
+            println!("INF: export {}", entry.src.to_slash().unwrap());
+            bundle.files.push(BundleFile { file: entry.src.to_slash().unwrap(), cif_line: String::from(line.trim()), org: forg.is_some() });
+        }
+        if bundle.files.is_empty() {
+            println!("WRN: nothing to export");
+            return;
+        }
+        fs::create_dir_all(&self.output).unwrap();
+        fs::write(self.output.join(BUNDLE_JSON), serde_json::to_string_pretty(&bundle).unwrap() + "\n").unwrap();
+        println!("INF: {} overrides are exported to {:?}", bundle.files.len(), self.output);
+    }
+}
+
+impl Import {
+    pub fn handler(&self, layers: &audk::Layers) {
+        let bundle: Bundle = match fs::read(self.bundle.join(BUNDLE_JSON)) {
+            Ok(buf) => match serde_json::from_slice(&buf) {
+                Ok(bundle) => bundle,
+                Err(e) => {
+                    println!("ERR: invalid format of bundle: {}", e);
+                    return;
+                }
+            },
+            Err(_) => {
+                println!("ERR: want bundle, but {} is not in: {:?}", BUNDLE_JSON, self.bundle);
+                return;
+            }
+        };
+        let top = layers.top().expect("ERR: oemovrd is neither given in cmdline or json");
+        let cif = top.cif.as_ref().expect("ERR: cif is None in json.");
+        if !cif.is_file() {
+            println!("ERR: want override.cif, but not a file: {:?}", cif);
+            return;
+        }
+        let dst = top.dst.as_ref().expect("ERR: dst is None in json.");
+        if !dst.is_dir() {
+            println!("ERR: want override.dst, but not a dir: {:?}", dst);
+            return;
+        }
+        let org = &top.org;
+        let lower_dsts = layers.lower_dsts();
+        println!("INF: import {} overrides of {} at {}", bundle.files.len(), bundle.layer, bundle.commit.as_deref().unwrap_or("unknown commit"));
+
+        let home = std::path::PathBuf::from(audk::FWTO_WS).join(IMPORT_HOME);
+        if home.is_dir() {
+            fs::remove_dir_all(&home).unwrap();
+        }
+        fs::create_dir_all(&home).unwrap();
+        let empty = home.join(".empty");
+        fs::write(&empty, b"").unwrap();
+
+        let mut outcomes = Vec::new();
+        for file in &bundle.files {
+            let fsrc   = std::path::PathBuf::from_slash(&file.file);
+            let fdst   = dst.join(&fsrc);
+            let base   = if file.org { self.bundle.join(BUNDLE_ORG).join(&fsrc) } else { empty.clone() };
+            let remote = self.bundle.join(BUNDLE_OVRD).join(&fsrc);
+            let outcome = if fdst.is_file() {
+                // [0]: overridden here, keep it as it is
+                if fs::read(&fdst).ok() == fs::read(&remote).ok() { Outcome::Unchanged } else { Outcome::Overridden }
+            } else if !fsrc.is_file() {
+                // [1]: override-only file is added as it is, or codebase is missing here
+                if file.org {
+                    Outcome::Missing
+                } else {
+                    fs::create_dir_all(fdst.parent().unwrap()).unwrap();
+                    ffs::copy(&remote, &fdst).unwrap();
+                    subcmd_ovrd::Ovrd::new(&fsrc, false, true).cif_add(cif, dst);
+                    Outcome::Applied
+                }
+            } else {
+                // [2]: three-way merge of codebase here, with the override from its original
+                let merged = home.join(IMPORT_MERGED).join(&fsrc);
+                fs::create_dir_all(merged.parent().unwrap()).unwrap();
+                match git::merge_file(&fsrc, &base, &remote, [IMPORT_NEW_, IMPORT_OLD_, BUNDLE_OVRD]) {
+                    Some((result, true)) => {
+                        subcmd_ovrd::Ovrd::new(&fsrc, false, false).override_add(cif, dst, org, &lower_dsts);
+                        ffs::force_rw(&fdst).unwrap();
+                        fs::write(&fdst, &result).unwrap();
+                        fs::write(&merged, &result).unwrap();
+                        Outcome::Applied
+                    },
+                    Some((result, false)) => {
+                        fs::write(&merged, &result).unwrap();
+                        Outcome::Conflict
+                    },
+                    None => {
+                        ffs::copy(&remote, &merged).unwrap();
+                        Outcome::Conflict
+                    },
+                }
+            };
+            outcomes.push((file, outcome));
+        }
+        ffs::remove_file(&empty).unwrap();
+
+        println!("---------------------------");
+        for outcome in [Outcome::Applied, Outcome::Unchanged, Outcome::Conflict, Outcome::Overridden, Outcome::Missing] {
+            for (file, _) in outcomes.iter().filter(|(_, o)| *o == outcome) {
+                println!("  {:<12} {}", outcome.name(), file.file);
+            }
+        }
+        let count = |outcome: Outcome| outcomes.iter().filter(|(_, o)| *o == outcome).count();
+        println!("INF: {} applied, {} unchanged, {} not applied", count(Outcome::Applied), count(Outcome::Unchanged), outcomes.len() - count(Outcome::Applied) - count(Outcome::Unchanged));
+        if count(Outcome::Conflict) > 0 {
+            println!("INF: conflicts are in {:?}, resolve them and override by hand", home.join(IMPORT_MERGED));
+        }
+        if count(Outcome::Overridden) > 0 {
+            println!("WRN: overridden files differ from the bundle, compare them with {:?}", self.bundle.join(BUNDLE_OVRD));
+        }
+    }
+}
@@ -18,12 +18,35 @@ use crate::audk;
 use crate::subcmd_ovrd;
 use crate::libs::ffs;
 use crate::libs::git;
+use crate::libs::hook;
+use crate::libs::tool;
 
 #[derive(StructOpt, Debug)]
 pub struct Cbup {
     /// Commit to be extract
-    #[structopt(short, long, parse(from_str))]
-    pub commit          : String,
+    #[structopt(short, long, parse(from_str), required_unless_one = &["cont", "status", "abort", "resolved", "skip", "ours", "theirs"])]
+    pub commit          : Option<String>,
+    /// Continue the session, pending files without conflict markers are resolved
+    #[structopt(name = "cont", long = "continue", conflicts_with_all = &["commit", "status", "abort"])]
+    pub cont            : bool,
+    /// Show state of files in the session
+    #[structopt(long, conflicts_with_all = &["commit", "abort"])]
+    pub status          : bool,
+    /// Abort the session, and reset workspace to its commit
+    #[structopt(long, conflicts_with = "commit")]
+    pub abort           : bool,
+    /// Mark files resolved, and copy their merged results to override dst
+    #[structopt(long, parse(from_os_str), conflicts_with = "commit")]
+    pub resolved        : Vec<std::path::PathBuf>,
+    /// Mark files skipped, and keep their overrides as they are
+    #[structopt(long, parse(from_os_str), conflicts_with = "commit")]
+    pub skip            : Vec<std::path::PathBuf>,
+    /// Resolve files by our overrides as they are, e.g. unmergeable binaries
+    #[structopt(long, parse(from_os_str), conflicts_with = "commit")]
+    pub ours            : Vec<std::path::PathBuf>,
+    /// Resolve files by files of new codebase, and drop our changes of them
+    #[structopt(long, parse(from_os_str), conflicts_with = "commit")]
+    pub theirs          : Vec<std::path::PathBuf>,
     #[structopt(flatten)]
     pub flags           : CbupFlags,
 }
@@ -33,81 +56,595 @@ pub struct CbupFlags {
     /// Extract codebase-pure module or package diffs
     #[structopt(long)]
     pub pure            : bool,
+    /// Open every pending file of the session by mergetool of json
+    #[structopt(long)]
+    pub merge_tool      : bool,
+    /// Similarity threshold of renames, e.g. 60%, default 75% or renames.find_renames of json
+    #[structopt(long, parse(try_from_str = git::similarity))]
+    pub find_renames    : Option<String>,
+    /// Similarity threshold of copies from files we override, e.g. 90%, copy detection is off if not given
+    #[structopt(long, parse(try_from_str = git::similarity))]
+    pub find_copies     : Option<String>,
+    /// Rename map to force or deny pairings, e.g. renames.map written by last cbup
+    #[structopt(long, parse(from_os_str))]
+    pub rename_map      : Option<std::path::PathBuf>,
+    /// Policy of overrides whose originals are deleted upstream: drop, keep as orphans, or ask for each
+    #[structopt(long, default_value = "drop", possible_values = &["drop", "keep", "ask"])]
+    pub deleted         : String,
 }
 
 const CBUP_HOME         : &str = "cbup";
 const CBUP_OLD_         : &str = "base.old";
 const CBUP_NEW_         : &str = "base.new";
 const CBUP_OVRD         : &str = "ovrd";
+const CBUP_MERGED       : &str = "merged";
+const CBUP_SESSION      : &str = "session.json";
+const CBUP_NOT_R        : &str = "!R";
+const CBUP_R            : &str = "R";
+const CBUP_C            : &str = "C";
+const CBUP_A            : &str = "A";
+const CBUP_RENAME_MAP   : &str = "renames.map";
+const RENAME_PAIR       : &str = "pair";
+const RENAME_DENY       : &str = "deny";
 
-impl Cbup {
-    pub fn handler(&self, opt_oemovrd: &audk::StdOvrd, cfg_oemovrd: &Option<&audk::StdOvrd>, cfg_ibvovrd: &Option<&audk::StdOvrd>) {
-        let cif = if let Some(cif) = &opt_oemovrd.cif {
-            cif
-        } else {
-            cfg_oemovrd.expect("ERR: oemovrd is neither given in cmdline or json").cif.as_ref().expect("ERR: cif is None in json.")
+/// A pairing of old and new file, R<score> or C<score> detected by git, or pair and deny by hand
+#[derive(Debug, Clone)]
+struct Rename {
+    kind                : String,
+    old                 : std::path::PathBuf,
+    new                 : std::path::PathBuf,
+}
+
+impl Rename {
+    /// <kind><TAB><old><TAB><new>, as git shows and rename map is, paths may have spaces
+    fn parse(line: &str) -> Option<Self> {
+        let mut part = line.trim_end_matches('\r').split('\t');
+        let kind = part.next()?.trim().to_string();
+        let old  = std::path::PathBuf::from(part.next()?);
+        let new  = std::path::PathBuf::from(part.next()?);
+        if kind.is_empty() || part.next().is_some() {
+            return None;
+        }
+        Some(Self { kind, old, new })
+    }
+
+    fn is(&self, old: &std::path::Path, new: &std::path::Path) -> bool {
+        self.old == old && self.new == new
+    }
+}
+
+/// Rename policy of cbup, from cmdline, or json, or default
+struct RenamePolicy {
+    find_renames        : String,
+    find_copies         : Option<String>,
+    /// Pairings of the rename map, which is fed back by hand
+    map                 : Vec<Rename>,
+}
+
+impl RenamePolicy {
+    fn new(flags: &CbupFlags, cfg_renames: &Option<&audk::Renames>) -> Result<Self, String> {
+        let find_renames = match (&flags.find_renames, cfg_renames.and_then(|r| r.find_renames.as_ref())) {
+            (Some(pct), _) => pct.clone(),
+            (None, Some(pct)) => git::similarity(pct)?,
+            (None, None) => String::from("75%"),
+        };
+        let find_copies = match (&flags.find_copies, cfg_renames.and_then(|r| r.find_copies.as_ref())) {
+            (Some(pct), _) => Some(pct.clone()),
+            (None, Some(pct)) => Some(git::similarity(pct)?),
+            (None, None) => None,
         };
+        let mut map = Vec::new();
+        if let Some(fmap) = flags.rename_map.as_ref().or_else(|| cfg_renames.and_then(|r| r.rename_map.as_ref())) {
+            let text = fs::read_to_string(fmap).map_err(|e| format!("failed to read rename map {:?}: {}", fmap, e))?;
+            for line in text.lines().map(|l| l.trim()).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+                match Rename::parse(line) {
+                    Some(rename) => map.push(rename),
+                    None => println!("WRN: invalid line of rename map: {}", line),
+                }
+            }
+        }
+        Ok(Self { find_renames, find_copies, map })
+    }
+
+    fn is_denied(&self, old: &std::path::Path, new: &std::path::Path) -> bool {
+        self.map.iter().any(|r| r.kind == RENAME_DENY && r.is(old, new))
+    }
+
+    /// Renames of git which are not denied, and pairings by hand, with denied renames
+    fn renames(&self, commit: &String, show_files: &Vec<&Option<&std::path::PathBuf>>) -> (Vec<Rename>, Vec<Rename>) {
+        let mut renames = Vec::new();
+        let mut denied  = Vec::new();
+        let output = git::show_no_format(commit, false, &self.find_renames, "R", show_files, true);
+        if !output.status.success() {
+            println!("codebase_oemovrd.r: {:#?}", output);
+        }
+        for rename in String::from_utf8(output.stdout).unwrap().lines().filter_map(Rename::parse) {
+            if self.is_denied(&rename.old, &rename.new) {
+                denied.push(rename);
+            } else {
+                renames.push(rename);
+            }
+        }
+        renames.extend(self.map.iter().filter(|r| r.kind == RENAME_PAIR).cloned());
+        (renames, denied)
+    }
+
+    /// Write pairings of files we override, with those denied by hand, to be edited and fed back
+    fn write_map(&self, commit: &str, pairings: &[Rename]) {
+        let commit = git::rev_parse(commit).unwrap_or_else(|| commit.to_string());
+        let mut text = format!("# rename map of {}: <kind><TAB><old><TAB><new>, one pairing per line, paths may have spaces\r\n", commit);
+        text += "#   R<score>, C<score>: rename or copy detected by git\r\n";
+        text += "#   pair: pair old and new as a rename, even if git does not\r\n";
+        text += "#   deny: do not pair old and new, they are taken as delete and add\r\n";
+        text += "# edit it, and feed it back by cbup --rename-map <file>, or renames.rename_map in json\r\n";
+        let denied = self.map.iter().filter(|r| r.kind == RENAME_DENY);
+        for rename in pairings.iter().chain(denied) {
+            text += &format!("{}\t{}\t{}\r\n", rename.kind, rename.old.to_slash().unwrap(), rename.new.to_slash().unwrap());
+        }
+        let home = std::path::PathBuf::from(audk::FWTO_WS).join(CBUP_HOME);
+        if !home.is_dir() {
+            fs::create_dir_all(&home).unwrap();
+        }
+        fs::write(home.join(CBUP_RENAME_MAP), text).unwrap();
+    }
+}
+
+/// State of cbup, kept across runs until all files are resolved or skipped
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Session {
+    commit              : String,
+    /// Where merged results are copied to
+    dst                 : std::path::PathBuf,
+    files               : Vec<SessionFile>,
+    /// Overrides whose originals are deleted upstream
+    #[serde(default)]
+    deleted             : Vec<Deleted>,
+    /// Started by --pure, where auto-merged results are not applied
+    #[serde(default)]
+    pure                : bool,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Deleted {
+    file                : std::path::PathBuf,
+    /// Override is kept as an orphan for review, or dropped from cif
+    kept                : bool,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct SessionFile {
+    file                : std::path::PathBuf,
+    /// Diff tree where the file is in, !R, R or A
+    tree                : String,
+    state               : State,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum State {
+    Pending,
+    /// Binary, or failed to merge, which is resolved by --ours or --theirs only
+    Unmergeable,
+    AutoMerged,
+    Resolved,
+    Skipped,
+}
+
+impl State {
+    fn name(&self) -> &str {
+        match self {
+            State::Pending     => "pending",
+            State::Unmergeable => "unmergeable",
+            State::AutoMerged  => "auto-merged",
+            State::Resolved    => "resolved",
+            State::Skipped     => "skipped",
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        *self == State::Pending || *self == State::Unmergeable
+    }
+}
+
+impl Session {
+    fn home() -> std::path::PathBuf {
+        std::path::PathBuf::from(audk::FWTO_WS).join(CBUP_HOME)
+    }
+
+    fn load() -> Option<Self> {
+        let fsession = Self::home().join(CBUP_SESSION);
+        let text = fs::read_to_string(&fsession).ok()?;
+        match serde_json::from_str(&text) {
+            Ok(session) => Some(session),
+            Err(e) => {
+                println!("ERR: invalid format of {:?}: {}", fsession, e);
+                None
+            }
+        }
+    }
+
+    fn save(&self) {
+        let home = Self::home();
+        if !home.is_dir() {
+            fs::create_dir_all(&home).unwrap();
+        }
+        fs::write(home.join(CBUP_SESSION), serde_json::to_string_pretty(self).unwrap() + "\n").unwrap();
+    }
+
+    fn merged(&self, file: &SessionFile) -> std::path::PathBuf {
+        Self::home().join(&file.tree).join(CBUP_MERGED).join(&file.file)
+    }
+
+    /// Our override of the file before the session
+    fn local(&self, file: &SessionFile) -> std::path::PathBuf {
+        let local = Self::home().join(&file.tree).join(CBUP_OVRD).join(&file.file);
+        if local.is_file() { local } else { self.dst.join(&file.file) }
+    }
+
+    /// The file of new codebase
+    fn remote(&self, file: &SessionFile) -> std::path::PathBuf {
+        Self::home().join(&file.tree).join(CBUP_NEW_).join(&file.file)
+    }
+
+    /// Whether the merged result is there and without conflict markers
+    fn is_merged(&self, file: &SessionFile) -> bool {
+        let merged = self.merged(file);
+        match fs::read(&merged) {
+            Ok(text) if has_conflict_markers(&text) => {
+                println!("WRN: conflict markers are still in {:?}", merged);
+                false
+            },
+            Ok(_) => true,
+            Err(e) => {
+                println!("WRN: merged result of {:?} is missing, keep it pending: {}", file.file, e);
+                false
+            },
+        }
+    }
+
+    /// Copy the merged result to override dst
+    fn apply(&self, file: &SessionFile) {
+        let fdst = self.dst.join(&file.file);
+        ffs::copy(self.merged(file), &fdst).unwrap();
+    }
+
+    fn position(&self, file: &std::path::PathBuf) -> Option<usize> {
+        let file = file.to_slash().unwrap().replace('\\', "/");
+        let i = self.files.iter().position(|f| f.file.to_slash().unwrap().eq_ignore_ascii_case(&file));
+        if i.is_none() {
+            println!("WRN: {:?} is not in the session", file);
+        }
+        i
+    }
+
+    fn status(&self) {
+        println!("INF: cbup session of {}", self.commit);
+        for file in &self.files {
+            // added upstream, where we have an override only file before
+            let class = if file.tree == CBUP_A { "  (upstream adopted our file)" } else { "" };
+            println!("  {:<12} {:<4} {}{}", file.state.name(), file.tree, file.file.to_slash().unwrap(), class);
+        }
+        let count = |state: State| self.files.iter().filter(|f| f.state == state).count();
+        println!("INF: {} pending, {} unmergeable, {} auto-merged, {} resolved, {} skipped",
+            count(State::Pending), count(State::Unmergeable), count(State::AutoMerged), count(State::Resolved), count(State::Skipped));
+        if count(State::Pending) != 0 {
+            println!("INF: merged results are in {:?}, resolve them and run cbup --continue", Self::home().join("<tree>").join(CBUP_MERGED));
+        }
+        if count(State::Unmergeable) != 0 {
+            println!("INF: unmergeable files are resolved by cbup --ours <file> or --theirs <file> only");
+        }
+        if self.pure && count(State::AutoMerged) != 0 {
+            println!("INF: auto-merged results of --pure are not applied, they are in {:?}", Self::home().join("<tree>").join(CBUP_MERGED));
+        }
+        if !self.deleted.is_empty() {
+            println!("INF: {} overrides are deleted upstream", self.deleted.len());
+            for deleted in &self.deleted {
+                println!("  {:<12} {}", if deleted.kept { "kept" } else { "dropped" }, deleted.file.to_slash().unwrap());
+            }
+        }
+        if self.deleted.iter().any(|d| d.kept) {
+            println!("WRN: kept overrides are orphans, review them in {:?}, and drop them by ovrd --clean if they are not in use", Self::home().join(CBUP_NOT_R));
+        }
+    }
+}
+
+/// Ask whether to keep the override whose original is deleted upstream
+fn ask_keep(fsrc: &std::path::Path) -> bool {
+    loop {
+        print!("{:?} is deleted upstream, [d]rop or [k]eep its override? [d]: ", fsrc);
+        std::io::stdout().flush().unwrap();
+        let mut input = String::new();
+        if std::io::stdin().read_line(&mut input).unwrap() == 0 {
+            return false;
+        }
+        match input.trim().to_ascii_lowercase().as_str() {
+            "" | "d" | "drop" => return false,
+            "k" | "keep" => return true,
+            input => println!("WRN: invalid choice: {}", input),
+        }
+    }
+}
+
+fn has_conflict_markers(text: &[u8]) -> bool {
+    String::from_utf8_lossy(text).lines().any(|line| line.starts_with("<<<<<<< ") || line.starts_with(">>>>>>> "))
+}
+
+impl Cbup {
+    fn commit(&self) -> &String {
+        self.commit.as_ref().unwrap()
+    }
+
+    pub fn handler(&self, layers: &audk::Layers, mergetool: &Option<&audk::Tool>, cfg_renames: &Option<&audk::Renames>, scripts: &Option<&audk::Scripts>) {
+        let top = layers.top().expect("ERR: oemovrd is neither given in cmdline or json");
+        let cif = top.cif.as_ref().expect("ERR: cif is None in json.");
         if !cif.is_file() {
             println!("ERR: want override.cif, but not a file: {:?}", cif);
             return;
         }
 
-        let dst = if let Some(dst) = &opt_oemovrd.dst {
-            dst
-        } else {
-            cfg_oemovrd.expect("ERR: oemovrd is neither given in cmdline or json").dst.as_ref().expect("ERR: dst is None in json.")
-        };
+        let dst = top.dst.as_ref().expect("ERR: dst is None in json.");
         if !dst.is_dir() {
             println!("ERR: want override.dst, but not a dir: {:?}", dst);
             return;
         }
 
-        let org = if opt_oemovrd.org.is_some() {
-            &opt_oemovrd.org
-        } else {
-            &cfg_oemovrd.expect("ERR: oemovrd is neither given in cmdline or json").org
-        };
+        let org = &top.org;
 
-        let ibvovrd_dst = if let Some(ibvovrd) = cfg_ibvovrd.as_deref() {
-            &ibvovrd.dst
-        } else {
-            &None
+        if self.commit.is_none() {
+            return self.handle_session(mergetool, scripts);
+        }
+        if let Some(session) = Session::load() {
+            if session.files.iter().any(|f| f.state.is_open()) {
+                println!("ERR: cbup session of {} is in progress, run cbup --continue or --abort", session.commit);
+                return;
+            }
+        }
+
+        // rename map may be in 0.fwto, so read it before it is cleaned
+        let policy = match RenamePolicy::new(&self.flags, cfg_renames) {
+            Ok(policy) => policy,
+            Err(e) => {
+                println!("ERR: {}", e);
+                return;
+            }
         };
-        let ibvovrd_dst = &ibvovrd_dst.as_ref();
+        let lower_dsts = &layers.lower_dsts();
+        // commit may be relative to HEAD, which moves when overrides are committed
+        let commit = git::rev_parse(self.commit()).unwrap_or_else(|| self.commit().clone());
+        let mut session = Session { commit, dst: dst.to_path_buf(), files: Vec::new(), deleted: Vec::new(), pure: self.flags.pure };
+
+        // files changed by codebase
+        let output = git::show_no_format(&session.commit, true, "100%", "ACDMRT", &Vec::new(), false);
+        let files = String::from_utf8_lossy(&output.stdout).lines().map(std::path::PathBuf::from_slash).collect::<Vec<_>>();
+        if !hook::run_stage(scripts, "pre_cbup", &files) {
+            println!("ERR: cbup is vetoed by pre_cbup hook");
+            return;
+        }
 
-        git::reset_hard_and_clean_xfd(&self.commit);
-        self.codebase_oemovrd(cif, dst, org, ibvovrd_dst);
-        self.codebase_ibvovrd(cif, dst, org, ibvovrd_dst);
+        git::reset_hard_and_clean_xfd(self.commit());
+        // diff trees of the last session are out of date
+        if Session::home().is_dir() {
+            fs::remove_dir_all(Session::home()).unwrap();
+        }
+        self.codebase_oemovrd(cif, dst, org, lower_dsts, &policy, &mut session);
+        // walk lower layers in order, so diff trees of the higher layer win
+        for layer in layers.lower() {
+            self.codebase_lowerovrd(cif, dst, org, layer, lower_dsts, &policy);
+        }
         if self.flags.pure {
-            git::revert_no_commit(&self.commit);
+            git::revert_no_commit(self.commit());
+        }
+
+        self.session_start(&mut session);
+        if self.flags.merge_tool {
+            self.merge_pending(&mut session, mergetool);
         }
+        session.save();
+        session.status();
+        // overrides to merge, and those whose originals are deleted
+        let files = session.files.iter().map(|f| f.file.clone()).chain(session.deleted.iter().map(|d| d.file.clone())).collect::<Vec<_>>();
+        hook::run_stage(scripts, "post_cbup", &files);
     }
 
-    fn codebase_oemovrd(&self, cif: &std::path::PathBuf, dst: &std::path::PathBuf, org: &Option<std::path::PathBuf>, ibvovrd_dst: &Option<&std::path::PathBuf>) {
-        let find_renames = "75%";
-        let show_files   = vec![ibvovrd_dst];
+    /// Files of new codebase are to be merged into overrides, those merged cleanly by git are applied at once,
+    /// unless it is --pure
+    fn session_start(&self, session: &mut Session) {
+        let home = Session::home();
+        if !home.is_dir() {
+            fs::create_dir_all(&home).unwrap();
+        }
+        let empty = home.join(".empty");
+        fs::write(&empty, b"").unwrap();
+        for tree in [CBUP_NOT_R, CBUP_R, CBUP_A] {
+            let t_path = home.join(tree);
+            for fsrc in tool::files_under(&t_path.join(CBUP_NEW_)) {
+                let fdst = session.dst.join(&fsrc);
+                if !fdst.is_file() {
+                    continue;
+                }
+                let base   = t_path.join(CBUP_OLD_).join(&fsrc);
+                // without base, every difference is a conflict
+                let base   = if base.is_file() { base } else { empty.clone() };
+                let file   = SessionFile { file: fsrc, tree: String::from(tree), state: State::Pending };
+                let local  = session.local(&file);
+                let remote = session.remote(&file);
+                let merged = session.merged(&file);
+                fs::create_dir_all(merged.parent().unwrap()).unwrap();
+                let state = match git::merge_file(&local, &base, &remote, [CBUP_OVRD, CBUP_OLD_, CBUP_NEW_]) {
+                    Some((result, clean)) => {
+                        fs::write(&merged, result).unwrap();
+                        if clean { State::AutoMerged } else { State::Pending }
+                    },
+                    // ours is in merged for review, but it is never taken without --ours
+                    None => {
+                        ffs::copy(&local, &merged).unwrap();
+                        State::Unmergeable
+                    },
+                };
+                let file = SessionFile { state, ..file };
+                if state == State::AutoMerged && !session.pure {
+                    session.apply(&file);
+                }
+                session.files.push(file);
+            }
+        }
+        ffs::remove_file(&empty).unwrap();
+    }
 
-        let not_r_path = std::path::PathBuf::from(audk::FWTO_WS).join(CBUP_HOME).join("!R");
-        if self.flags.pure {
-            let output = git::show_no_format(&self.commit, true, find_renames, "A", &show_files, true);
-            if !output.status.success() {
-                println!("codebase_oemovrd.a: {:#?}", output);
+    fn handle_session(&self, mergetool: &Option<&audk::Tool>, scripts: &Option<&audk::Scripts>) {
+        let mut session = match Session::load() {
+            Some(session) => session,
+            None => {
+                println!("ERR: no cbup session, start one by cbup -c <commit>");
+                return;
+            }
+        };
+        if self.status {
+            return session.status();
+        }
+        if self.abort {
+            println!("INF: abort cbup session of {}", session.commit);
+            git::reset_hard_and_clean_xfd(&session.commit);
+            ffs::remove_file(Session::home().join(CBUP_SESSION)).unwrap();
+            println!("INF: diff trees are kept for review until next cbup: {:?}", Session::home());
+            return;
+        }
+        let pending = session.files.iter().filter(|f| f.state.is_open()).map(|f| f.file.clone()).collect::<Vec<_>>();
+        if !hook::run_stage(scripts, "pre_cbup", &pending) {
+            println!("ERR: cbup is vetoed by pre_cbup hook");
+            return;
+        }
+        let before = session.files.iter().map(|f| f.state).collect::<Vec<_>>();
+        let marked = self.resolved.iter().filter_map(|file| session.position(file)).collect::<Vec<_>>();
+        for i in marked {
+            if session.files[i].state == State::Unmergeable {
+                println!("WRN: {:?} is unmergeable, resolve it by --ours or --theirs", session.files[i].file);
+                continue;
+            }
+            if !session.is_merged(&session.files[i]) {
+                continue;
+            }
+            session.apply(&session.files[i]);
+            session.files[i].state = State::Resolved;
+        }
+        // pick a side as the merged result
+        let picks = self.ours.iter().map(|file| (file, true)).chain(self.theirs.iter().map(|file| (file, false)));
+        for (file, ours) in picks {
+            let i = match session.position(file) {
+                Some(i) => i,
+                None => continue,
+            };
+            let side = if ours { session.local(&session.files[i]) } else { session.remote(&session.files[i]) };
+            let merged = session.merged(&session.files[i]);
+            if let Err(e) = ffs::copy(&side, &merged) {
+                println!("WRN: failed to take {:?} for {:?}: {}", side, session.files[i].file, e);
+                continue;
+            }
+            session.apply(&session.files[i]);
+            session.files[i].state = State::Resolved;
+        }
+        let marked = self.skip.iter().filter_map(|file| session.position(file)).collect::<Vec<_>>();
+        for i in marked {
+            session.files[i].state = State::Skipped;
+        }
+        if self.cont {
+            // pending files, which are merged by hand and without conflict markers
+            for i in 0..session.files.len() {
+                let file = &session.files[i];
+                if file.state == State::Pending && session.is_merged(file) {
+                    session.apply(file);
+                    session.files[i].state = State::Resolved;
+                }
+            }
+        }
+        if self.flags.merge_tool {
+            self.merge_pending(&mut session, mergetool);
+        }
+        session.save();
+        session.status();
+        // files resolved or skipped in this run
+        let files = session.files.iter().zip(before).filter(|(f, state)| f.state != *state).map(|(f, _)| f.file.clone()).collect::<Vec<_>>();
+        hook::run_stage(scripts, "post_cbup", &files);
+    }
+
+    /// Open every pending file of the session by mergetool one after another, and resolve them
+    fn merge_pending(&self, session: &mut Session, mergetool: &Option<&audk::Tool>) {
+        let tool = match mergetool {
+            Some(tool) => tool,
+            None => {
+                println!("ERR: mergetool is not in json");
+                return;
+            }
+        };
+        for i in 0..session.files.len() {
+            let file = &session.files[i];
+            if file.state != State::Pending {
+                continue;
+            }
+            let base   = Session::home().join(&file.tree).join(CBUP_OLD_).join(&file.file);
+            let local  = session.local(file);
+            let remote = session.remote(file);
+            let merged = session.merged(file);
+            println!("INF: merging {:?}", file.file);
+            let before = fs::read(&merged).ok();
+            let status = match tool::launch(&tool.cmd, &tool.args, &[("base", &base), ("local", &local), ("remote", &remote), ("merged", &merged)]) {
+                Some(status) => status,
+                None => break,
+            };
+            let is_resolved = if tool.trust_exit_code.unwrap_or(true) { status.success() } else { fs::read(&merged).ok() != before };
+            if is_resolved {
+                session.apply(file);
+                session.files[i].state = State::Resolved;
             } else {
-                let git_show_result = String::from_utf8(output.clone().stdout).unwrap();
-                for line in git_show_result.lines() {
-                    let fsrc = std::path::PathBuf::from(line);
-                    // [0]: light override it
-                    subcmd_ovrd::Ovrd::new(&fsrc, false, true).override_add(cif, dst, org, ibvovrd_dst);
+                println!("WRN: {:?} is not resolved", file.file);
+            }
+        }
+    }
+
+    fn codebase_oemovrd(&self, cif: &std::path::PathBuf, dst: &std::path::PathBuf, org: &Option<std::path::PathBuf>, lower_dsts: &[&std::path::PathBuf], policy: &RenamePolicy, session: &mut Session) {
+        let find_renames = policy.find_renames.as_str();
+        let lower_dsts_  = lower_dsts.iter().map(|lower_dst| Some(*lower_dst)).collect::<Vec<_>>();
+        let show_files   = lower_dsts_.iter().collect::<Vec<_>>();
+
+        let not_r_path = std::path::PathBuf::from(audk::FWTO_WS).join(CBUP_HOME).join(CBUP_NOT_R);
+        // denied renames are taken as delete and add, and olds of pairings by hand are not deleted
+        let (renames, denied) = policy.renames(self.commit(), &show_files);
+        let a_path = std::path::PathBuf::from(audk::FWTO_WS).join(CBUP_HOME).join(CBUP_A);
+        let output = git::show_no_format(self.commit(), true, find_renames, "A", &show_files, true);
+        if !output.status.success() {
+            println!("codebase_oemovrd.a: {:#?}", output);
+        } else {
+            let git_show_result = String::from_utf8(output.clone().stdout).unwrap();
+            let added = git_show_result.lines().map(std::path::PathBuf::from).chain(denied.iter().map(|r| r.new.clone()));
+            for fsrc in added {
+                let fdst = dst.join(&fsrc);
+                // [0]: upstream adopted our file, which is override only before?
+                if fdst.is_file() {
+                    // [1]: diff trees for better compare, base.old is empty as it is added
+                    let _old = a_path.join(CBUP_OLD_).join(&fsrc);
+                    let _new = a_path.join(CBUP_NEW_).join(&fsrc);
+                    let ovrd = a_path.join(CBUP_OVRD).join(&fsrc);
+                    // create base.new
+                    git::create_file_from(self.commit(), &fsrc, &_new, None);
+                    // create ovrd
+                    git::create_file_from(self.commit(), &fdst, &ovrd, None);
+                    // create base.old
+                    fs::create_dir_all(_old.parent().unwrap()).unwrap();
+                    fs::write(&_old, b"").unwrap();
+                }
+                if self.flags.pure {
+                    // [2]: light override it
+                    subcmd_ovrd::Ovrd::new(&fsrc, false, true).override_add(cif, dst, org, lower_dsts);
                 }
             }
         }
-        let output = git::show_no_format(&self.commit, true, find_renames, "D", &show_files, true);
+        let output = git::show_no_format(self.commit(), true, find_renames, "D", &show_files, true);
         if !output.status.success() {
             println!("codebase_oemovrd.d: {:#?}", output);
         } else {
             let git_show_result = String::from_utf8(output.clone().stdout).unwrap();
-            for line in git_show_result.lines() {
-                let fsrc = std::path::PathBuf::from(line);
+            let deleted = git_show_result.lines().map(std::path::PathBuf::from).chain(denied.iter().map(|r| r.old.clone()));
+            for fsrc in deleted.filter(|fsrc| !renames.iter().any(|r| &r.old == fsrc)) {
                 let fdst = dst.join(&fsrc);
                 // [0]: also we have it?
                 if fdst.is_file() {
@@ -115,15 +652,23 @@ impl Cbup {
                     let _old = not_r_path.join(CBUP_OLD_).join(&fsrc);
                     let ovrd = not_r_path.join(CBUP_OVRD).join(&fsrc);
                     // create base.old
-                    git::create_file_from(&self.commit, &fsrc, &_old, Some("~"));
+                    git::create_file_from(self.commit(), &fsrc, &_old, Some("~"));
                     // create ovrd
-                    git::create_file_from(&self.commit, &fdst, &ovrd, None);
-                    // [2]: update override
-                    subcmd_ovrd::Ovrd::new(&fsrc, true, false).override_del(cif, dst, org);
+                    git::create_file_from(self.commit(), &fdst, &ovrd, None);
+                    // [2]: update override, or keep it as an orphan for review
+                    let kept = match self.flags.deleted.as_str() {
+                        "keep" => true,
+                        "ask"  => ask_keep(&fsrc),
+                        _      => false,
+                    };
+                    if !kept {
+                        subcmd_ovrd::Ovrd::new(&fsrc, true, false).override_del(cif, dst, org);
+                    }
+                    session.deleted.push(Deleted { file: fsrc, kept });
                 }
             }
         }
-        let output = git::show_no_format(&self.commit, true, find_renames, "M", &show_files, true);
+        let output = git::show_no_format(self.commit(), true, find_renames, "M", &show_files, true);
         if !output.status.success() {
             println!("codebase_oemovrd.m: {:#?}", output);
         } else {
@@ -138,225 +683,205 @@ impl Cbup {
                     let _new = not_r_path.join(CBUP_NEW_).join(&fsrc);
                     let ovrd = not_r_path.join(CBUP_OVRD).join(&fsrc);
                     // create base.old
-                    git::create_file_from(&self.commit, &fsrc, &_old, Some("~"));
+                    git::create_file_from(self.commit(), &fsrc, &_old, Some("~"));
                     // create base.new
-                    git::create_file_from(&self.commit, &fsrc, &_new, None);
+                    git::create_file_from(self.commit(), &fsrc, &_new, None);
                     // create ovrd
-                    git::create_file_from(&self.commit, &fdst, &ovrd, None);
+                    git::create_file_from(self.commit(), &fdst, &ovrd, None);
                     // [2]: update override
                     if !self.flags.pure {
-                        subcmd_ovrd::Ovrd::new(&fsrc, false, false).override_add(cif, dst, org, ibvovrd_dst);
+                        subcmd_ovrd::Ovrd::new(&fsrc, false, false).override_add(cif, dst, org, lower_dsts);
                     } else {
-                        subcmd_ovrd::Ovrd::new(&fsrc, false,  true).override_add(cif, dst, org, ibvovrd_dst);
+                        subcmd_ovrd::Ovrd::new(&fsrc, false,  true).override_add(cif, dst, org, lower_dsts);
                     }
                 } else if self.flags.pure {
-                    subcmd_ovrd::Ovrd::new(&fsrc, false, true).override_add(cif, dst, org, ibvovrd_dst);
+                    subcmd_ovrd::Ovrd::new(&fsrc, false, true).override_add(cif, dst, org, lower_dsts);
                 }
             }
         }
-        let output = git::show_no_format(&self.commit, false, "100%", "R", &show_files, true);
-        if output.status.success() {
-            let git_show_result = String::from_utf8(output.clone().stdout).unwrap();
-            for line in git_show_result.lines() {
-                let mut  part = line.split_whitespace();
-                let _rpercent = part.next().unwrap();
-                let _old_fsrc = std::path::PathBuf::from(part.next().unwrap());
-                let _new_fsrc = std::path::PathBuf::from(part.next().unwrap());
-                let fdst = dst.join(&_old_fsrc);
-                // [0]: also we have it?
-                if fdst.is_file() {
-                    // [1]: replace old override with new override
-                    if !self.flags.pure {
-                        let old_ovrd = subcmd_ovrd::Ovrd::new(&_old_fsrc, false, false);
-                        let new_ovrd = subcmd_ovrd::Ovrd::new(&_new_fsrc, false, false);
-                        old_ovrd.override_replace_with(&new_ovrd, cif, dst, org, ibvovrd_dst);
-                    } else {
-                        let old_ovrd = subcmd_ovrd::Ovrd::new(&_old_fsrc, false, true);
-                        let new_ovrd = subcmd_ovrd::Ovrd::new(&_new_fsrc, false, true);
-                        old_ovrd.override_replace_with(&new_ovrd, cif, dst, org, ibvovrd_dst);
-                    }
-                } else if self.flags.pure {
-                    subcmd_ovrd::Ovrd::new(&_new_fsrc, false, true).override_add(cif, dst, org, ibvovrd_dst);
+        for rename in renames.iter().filter(|r| r.kind == "R100") {
+            let fdst = dst.join(&rename.old);
+            // [0]: also we have it?
+            if fdst.is_file() {
+                // [1]: replace old override with new override
+                let old_ovrd = subcmd_ovrd::Ovrd::new(&rename.old, false, self.flags.pure);
+                let new_ovrd = subcmd_ovrd::Ovrd::new(&rename.new, false, self.flags.pure);
+                old_ovrd.override_replace_with(&new_ovrd, cif, dst, org, lower_dsts);
+            } else if self.flags.pure {
+                subcmd_ovrd::Ovrd::new(&rename.new, false, true).override_add(cif, dst, org, lower_dsts);
+            }
+        }
+        let r_path = std::path::PathBuf::from(audk::FWTO_WS).join(CBUP_HOME).join(CBUP_R);
+        let mut pairings = Vec::new();
+        for rename in &renames {
+            let (_old_fsrc, _new_fsrc) = (&rename.old, &rename.new);
+            let fdst = dst.join(_old_fsrc);
+            // [0]: also we have it?
+            if fdst.is_file() {
+                // pairings of files we override are in rename map
+                pairings.push(rename.clone());
+                if rename.kind == "R100" {
+                    continue;
                 }
+
+                // [1]: build files tree like M, all path align to _new_fsrc for better compare
+                let _old = r_path.join(CBUP_OLD_).join(_new_fsrc);
+                let _new = r_path.join(CBUP_NEW_).join(_new_fsrc);
+                let ovrd = r_path.join(CBUP_OVRD).join(_new_fsrc);
+                // create base.old
+                git::create_file_from(self.commit(), _old_fsrc, &_old, Some("~"));
+                // create base.new
+                git::create_file_from(self.commit(), _new_fsrc, &_new, None);
+                // create ovrd
+                git::create_file_from(self.commit(), &fdst, &ovrd, None);
+                // [2]: replace old override with new override like R100%
+                let old_ovrd = subcmd_ovrd::Ovrd::new(_old_fsrc, false, self.flags.pure);
+                let new_ovrd = subcmd_ovrd::Ovrd::new(_new_fsrc, false, self.flags.pure);
+                old_ovrd.override_replace_with(&new_ovrd, cif, dst, org, lower_dsts);
+            } else if self.flags.pure && rename.kind != "R100" {
+                subcmd_ovrd::Ovrd::new(_new_fsrc, false, true).override_add(cif, dst, org, lower_dsts);
             }
-        } else {
-            println!("codebase_oemovrd.r100: {:#?}", output);
         }
-        let r_path = std::path::PathBuf::from(audk::FWTO_WS).join(CBUP_HOME).join("R75");
-        let output = git::show_no_format(&self.commit, false, find_renames, "R", &show_files, true);
-        if output.status.success() {
-            let r_path_parent = r_path.parent().unwrap();
-            if !r_path_parent.is_dir() {
-                fs::create_dir_all(&r_path_parent).unwrap();
+        // copies of files we override, which are only for review, as the originals are still there
+        if let Some(find_copies) = policy.find_copies.as_ref() {
+            let c_path = std::path::PathBuf::from(audk::FWTO_WS).join(CBUP_HOME).join(CBUP_C);
+            let output = git::show_copies(self.commit(), find_copies, &show_files, true);
+            if !output.status.success() {
+                println!("codebase_oemovrd.c: {:#?}", output);
             }
-            let r_log_path = r_path_parent.join("R75.log");
-            let mut r_log = fs::OpenOptions::new().create(true).write(true).open(&r_log_path).unwrap();
-
-            let git_show_result = String::from_utf8(output.clone().stdout).unwrap();
-            for line in git_show_result.lines() {
-                let mut  part = line.split_whitespace();
-                let _rpercent = part.next().unwrap();
-                let _old_fsrc = std::path::PathBuf::from(part.next().unwrap());
-                let _new_fsrc = std::path::PathBuf::from(part.next().unwrap());
-                let fdst = std::path::PathBuf::from(dst).join(&_old_fsrc);
-                // [0]: also we have it?
-                if fdst.is_file() {
-                    if _rpercent == "R100" {
-                        continue;
-                    } else {
-                        // log rename files for R75%
-                        let line_with_new_line = String::from(line) + "\r\n";
-                        r_log.write(line_with_new_line.as_bytes()).unwrap();
-                    }
-
-                    // [1]: build files tree like M, all path align to _new_fsrc for better compare
-                    let _old = r_path.join(CBUP_OLD_).join(&_new_fsrc);
-                    let _new = r_path.join(CBUP_NEW_).join(&_new_fsrc);
-                    let ovrd = r_path.join(CBUP_OVRD).join(&_new_fsrc);
-                    // create base.old
-                    git::create_file_from(&self.commit, &_old_fsrc, &_old, Some("~"));
-                    // create base.new
-                    git::create_file_from(&self.commit, &_new_fsrc, &_new, None);
-                    // create ovrd
-                    git::create_file_from(&self.commit, &fdst, &ovrd, None);
-                    // [2]: replace old override with new override like R100%
-                    if !self.flags.pure {
-                        let old_ovrd = subcmd_ovrd::Ovrd::new(&_old_fsrc, false, false);
-                        let new_ovrd = subcmd_ovrd::Ovrd::new(&_new_fsrc, false, false);
-                        old_ovrd.override_replace_with(&new_ovrd, cif, dst, org, ibvovrd_dst);
-                    } else {
-                        let old_ovrd = subcmd_ovrd::Ovrd::new(&_old_fsrc, false, true);
-                        let new_ovrd = subcmd_ovrd::Ovrd::new(&_new_fsrc, false, true);
-                        old_ovrd.override_replace_with(&new_ovrd, cif, dst, org, ibvovrd_dst);
-                    }
-                } else if self.flags.pure {
-                    subcmd_ovrd::Ovrd::new(&_new_fsrc, false, true).override_add(cif, dst, org, ibvovrd_dst);
+            for copy in String::from_utf8(output.stdout).unwrap().lines().filter_map(Rename::parse) {
+                let fdst = dst.join(&copy.old);
+                if !fdst.is_file() || policy.is_denied(&copy.old, &copy.new) {
+                    continue;
                 }
+                println!("INF: {:?} is copied from {:?} we override", copy.new, copy.old);
+                git::create_file_from(self.commit(), &copy.old, &c_path.join(CBUP_OLD_).join(&copy.new), Some("~"));
+                git::create_file_from(self.commit(), &copy.new, &c_path.join(CBUP_NEW_).join(&copy.new), None);
+                git::create_file_from(self.commit(), &fdst, &c_path.join(CBUP_OVRD).join(&copy.new), None);
+                pairings.push(copy);
             }
-            if r_log.metadata().unwrap().len() == 0 {
-                ffs::remove_file(&r_log_path).unwrap();
-            } else {
-                fs::rename(&r_log_path, r_path.join("R75.log")).unwrap();
-            }
-        } else {
-            println!("codebase_oemovrd.r75: {:#?}", output);
+        }
+        if !pairings.is_empty() || !policy.map.is_empty() {
+            policy.write_map(self.commit(), &pairings);
         }
     }
 
-    fn codebase_ibvovrd(&self, cif: &std::path::PathBuf, dst: &std::path::PathBuf, org: &Option<std::path::PathBuf>, ibvovrd_dst: &Option<&std::path::PathBuf>) {
-        if let Some(ibvovrd_dst) = ibvovrd_dst {
-            if !ibvovrd_dst.is_dir() {
-                println!("WRN: ibvovrd.dst is set but not a dir: {:?}", &ibvovrd_dst);
+    fn codebase_lowerovrd(&self, cif: &std::path::PathBuf, dst: &std::path::PathBuf, org: &Option<std::path::PathBuf>, layer: &audk::Layer, lower_dsts: &[&std::path::PathBuf], policy: &RenamePolicy) {
+        let lower_dst = if let Some(lower_dst) = layer.dst.as_ref() {
+            if !lower_dst.is_dir() {
+                println!("WRN: {}.dst is set but not a dir: {:?}", layer.name, &lower_dst);
                 return;
             }
+            lower_dst
         } else {
             return;
-        }
-        let find_renames = "75%";
-        let show_files   = vec![ibvovrd_dst];
-        let not_r_path = std::path::PathBuf::from(audk::FWTO_WS).join(CBUP_HOME).join("!R");
-        let output = git::show_no_format(&self.commit, true, find_renames, "A", &show_files, false);
+        };
+        let find_renames = policy.find_renames.as_str();
+        let lower_dst_   = Some(lower_dst);
+        let show_files   = vec![&lower_dst_];
+        let not_r_path = std::path::PathBuf::from(audk::FWTO_WS).join(CBUP_HOME).join(CBUP_NOT_R);
+        let output = git::show_no_format(self.commit(), true, find_renames, "A", &show_files, false);
         if !output.status.success() {
-            println!("codebase_ibvovrd.a: {:#?}", output);
+            println!("codebase_lowerovrd.a: {:#?}", output);
         } else {
             let git_show_result = String::from_utf8(output.clone().stdout).unwrap();
             for line in git_show_result.lines() {
-                let fibv = std::path::PathBuf::from(line);
-                let fsrc = std::path::PathBuf::from(fibv.strip_prefix(&ibvovrd_dst.unwrap().to_slash().unwrap()).unwrap());
+                let flow = std::path::PathBuf::from(line);
+                let fsrc = std::path::PathBuf::from(flow.strip_prefix(&lower_dst.to_slash().unwrap()).unwrap());
                 let fdst = dst.join(&fsrc);
                 let _new = not_r_path.join(CBUP_NEW_).join(&fsrc);
                 if fdst.is_file() || _new.is_file() {
-                    // [1]: update diff trees (ibvovrd.A: keep old, update new)
-                    git::create_file_from(&self.commit, &fibv, &_new, None);
+                    // [1]: update diff trees (lowerovrd.A: keep old, update new)
+                    git::create_file_from(self.commit(), &flow, &_new, None);
                 } else if self.flags.pure {
                     if fsrc.is_file() {
-                        subcmd_ovrd::Ovrd::new(&fsrc, false, true).override_add(cif, dst, org, ibvovrd_dst);
+                        subcmd_ovrd::Ovrd::new(&fsrc, false, true).override_add(cif, dst, org, lower_dsts);
                     } else {
                         fs::OpenOptions::new().create(true).open(&fsrc).unwrap();
-                        subcmd_ovrd::Ovrd::new(&fsrc, false, true).override_add(cif, dst, org, ibvovrd_dst);
+                        subcmd_ovrd::Ovrd::new(&fsrc, false, true).override_add(cif, dst, org, lower_dsts);
                         ffs::remove_file(fsrc).unwrap();
                     }
                 }
             }
         }
-        let output = git::show_no_format(&self.commit, true, find_renames, "D", &show_files, false);
+        let output = git::show_no_format(self.commit(), true, find_renames, "D", &show_files, false);
         if !output.status.success() {
-            println!("codebase_ibvovrd.d: {:#?}", output);
+            println!("codebase_lowerovrd.d: {:#?}", output);
         } else {
             let git_show_result = String::from_utf8(output.clone().stdout).unwrap();
             for line in git_show_result.lines() {
-                let fibv = std::path::PathBuf::from(line);
-                let fsrc = std::path::PathBuf::from(fibv.strip_prefix(&ibvovrd_dst.unwrap().to_slash().unwrap()).unwrap());
+                let flow = std::path::PathBuf::from(line);
+                let fsrc = std::path::PathBuf::from(flow.strip_prefix(&lower_dst.to_slash().unwrap()).unwrap());
                 let fdst = dst.join(&fsrc);
                 let _old = not_r_path.join(CBUP_OLD_).join(&fsrc);
                 if fdst.is_file() || _old.is_file() {
-                    // [1]: update diff trees (ibvovrd.D: keep new, update old)
-                    git::create_file_from(&self.commit, &fibv, &_old, Some("~"));
+                    // [1]: update diff trees (lowerovrd.D: keep new, update old)
+                    git::create_file_from(self.commit(), &flow, &_old, Some("~"));
                 }
             }
         }
-        let output = git::show_no_format(&self.commit, true, find_renames, "M", &show_files, false);
+        let output = git::show_no_format(self.commit(), true, find_renames, "M", &show_files, false);
         if !output.status.success() {
-            println!("codebase_ibvovrd.m: {:#?}", output);
+            println!("codebase_lowerovrd.m: {:#?}", output);
         } else {
             let git_show_result = String::from_utf8(output.clone().stdout).unwrap();
             for line in git_show_result.lines() {
-                let fibv = std::path::PathBuf::from(line);
-                let fsrc = std::path::PathBuf::from(fibv.strip_prefix(&ibvovrd_dst.unwrap().to_slash().unwrap()).unwrap());
+                let flow = std::path::PathBuf::from(line);
+                let fsrc = std::path::PathBuf::from(flow.strip_prefix(&lower_dst.to_slash().unwrap()).unwrap());
                 let fdst = dst.join(&fsrc);
                 let _old = not_r_path.join(CBUP_OLD_).join(&fsrc);
                 let _new = not_r_path.join(CBUP_NEW_).join(&fsrc);
                 if fdst.is_file() || _old.is_file() || _new.is_file() {
-                    // [1]: update diff trees (ibvovrd.M: update old, update new)
-                    git::create_file_from(&self.commit, &fibv, &_old, Some("~"));
-                    git::create_file_from(&self.commit, &fibv, &_new, None);
+                    // [1]: update diff trees (lowerovrd.M: update old, update new)
+                    git::create_file_from(self.commit(), &flow, &_old, Some("~"));
+                    git::create_file_from(self.commit(), &flow, &_new, None);
                 } else if self.flags.pure {
                     if fsrc.is_file() {
-                        subcmd_ovrd::Ovrd::new(&fsrc, false, true).override_add(cif, dst, org, ibvovrd_dst);
+                        subcmd_ovrd::Ovrd::new(&fsrc, false, true).override_add(cif, dst, org, lower_dsts);
                     } else {
                         fs::OpenOptions::new().create(true).open(&fsrc).unwrap();
-                        subcmd_ovrd::Ovrd::new(&fsrc, false, true).override_add(cif, dst, org, ibvovrd_dst);
+                        subcmd_ovrd::Ovrd::new(&fsrc, false, true).override_add(cif, dst, org, lower_dsts);
                         ffs::remove_file(fsrc).unwrap();
                     }
                 }
             }
         }
-        let output = git::show_no_format(&self.commit, false, "100%", "R", &show_files, false);
+        let output = git::show_no_format(self.commit(), false, "100%", "R", &show_files, false);
         if !output.status.success() {
-            println!("codebase_ibvovrd.r100: {:#?}", output);
+            println!("codebase_lowerovrd.r100: {:#?}", output);
         } else {
             let git_show_result = String::from_utf8(output.clone().stdout).unwrap();
             for line in git_show_result.lines() {
-                let mut  part = line.split_whitespace();
+                let mut  part = line.split('\t');
                 let _rpercent = part.next().unwrap();
                 let _old_fsrc = std::path::PathBuf::from(part.next().unwrap());
                 let _new_fsrc = std::path::PathBuf::from(part.next().unwrap());
 
-                let fsrc = std::path::PathBuf::from(_new_fsrc.strip_prefix(&ibvovrd_dst.unwrap().to_slash().unwrap()).unwrap());
-                // [1]: update diff trees (ibvovrd.R: ignore)
+                let fsrc = std::path::PathBuf::from(_new_fsrc.strip_prefix(&lower_dst.to_slash().unwrap()).unwrap());
+                // [1]: update diff trees (lowerovrd.R: ignore)
                 if self.flags.pure {
                     if fsrc.is_file() {
-                        subcmd_ovrd::Ovrd::new(&fsrc, false, true).override_add(cif, dst, org, ibvovrd_dst);
+                        subcmd_ovrd::Ovrd::new(&fsrc, false, true).override_add(cif, dst, org, lower_dsts);
                     } else {
                         fs::OpenOptions::new().create(true).open(&fsrc).unwrap();
-                        subcmd_ovrd::Ovrd::new(&fsrc, false, true).override_add(cif, dst, org, ibvovrd_dst);
+                        subcmd_ovrd::Ovrd::new(&fsrc, false, true).override_add(cif, dst, org, lower_dsts);
                         ffs::remove_file(fsrc).unwrap();
                     }
                 }
             }
         }
 
-        let r_path = std::path::PathBuf::from(audk::FWTO_WS).join(CBUP_HOME).join("R75");
-        let output = git::show_no_format(&self.commit, false, find_renames, "R", &show_files, false);
+        let r_path = std::path::PathBuf::from(audk::FWTO_WS).join(CBUP_HOME).join(CBUP_R);
+        let output = git::show_no_format(self.commit(), false, find_renames, "R", &show_files, false);
         if output.status.success() {
             let git_show_result = String::from_utf8(output.clone().stdout).unwrap();
             for line in git_show_result.lines() {
-                let mut  part = line.split_whitespace();
+                let mut  part = line.split('\t');
                 let _rpercent = part.next().unwrap();
                 let _old_fsrc = std::path::PathBuf::from(part.next().unwrap());
                 let _new_fsrc = std::path::PathBuf::from(part.next().unwrap());
 
-                let fsrc = std::path::PathBuf::from(_new_fsrc.strip_prefix(&ibvovrd_dst.unwrap().to_slash().unwrap()).unwrap());
+                let fsrc = std::path::PathBuf::from(_new_fsrc.strip_prefix(&lower_dst.to_slash().unwrap()).unwrap());
                 let fdst = std::path::PathBuf::from(dst).join(&fsrc);
                 let _old = r_path.join(CBUP_OLD_).join(&fsrc);
                 let _new = r_path.join(CBUP_NEW_).join(&fsrc);
@@ -364,21 +889,43 @@ impl Cbup {
                     if _rpercent == "R100" {
                         continue;
                     }
-                    // [1]: update diff trees (ibvovrd.R: update old, update new)
-                    git::create_file_from(&self.commit, &_old_fsrc, &_old, Some("~"));
-                    git::create_file_from(&self.commit, &_new_fsrc, &_new, None);
+                    // [1]: update diff trees (lowerovrd.R: update old, update new)
+                    git::create_file_from(self.commit(), &_old_fsrc, &_old, Some("~"));
+                    git::create_file_from(self.commit(), &_new_fsrc, &_new, None);
                 } else if self.flags.pure {
                     if fsrc.is_file() {
-                        subcmd_ovrd::Ovrd::new(&fsrc, false, true).override_add(cif, dst, org, ibvovrd_dst);
+                        subcmd_ovrd::Ovrd::new(&fsrc, false, true).override_add(cif, dst, org, lower_dsts);
                     } else {
                         fs::OpenOptions::new().create(true).open(&fsrc).unwrap();
-                        subcmd_ovrd::Ovrd::new(&fsrc, false, true).override_add(cif, dst, org, ibvovrd_dst);
+                        subcmd_ovrd::Ovrd::new(&fsrc, false, true).override_add(cif, dst, org, lower_dsts);
                         ffs::remove_file(fsrc).unwrap();
                     }
                 }
             }
         } else {
-            println!("codebase_oemovrd.r75: {:#?}", output);
+            println!("codebase_lowerovrd.r75: {:#?}", output);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rename_parse_paths_with_spaces() {
+        let rename = Rename::parse("R087\tMdePkg/Old Dir/a b.c\tMdePkg/New Dir/a b.c\r").unwrap();
+        assert_eq!(rename.kind, "R087");
+        assert_eq!(rename.old, std::path::PathBuf::from("MdePkg/Old Dir/a b.c"));
+        assert_eq!(rename.new, std::path::PathBuf::from("MdePkg/New Dir/a b.c"));
+        assert!(rename.is(std::path::Path::new("MdePkg/Old Dir/a b.c"), std::path::Path::new("MdePkg/New Dir/a b.c")));
+    }
+
+    #[test]
+    fn rename_parse_invalid_lines() {
+        assert!(Rename::parse("deny MdePkg/a.c MdePkg/b.c").is_none());
+        assert!(Rename::parse("pair\tMdePkg/a.c").is_none());
+        assert!(Rename::parse("\tMdePkg/a.c\tMdePkg/b.c").is_none());
+        assert!(Rename::parse("pair\tMdePkg/a.c\tMdePkg/b.c\tMdePkg/c.c").is_none());
+    }
+}
@@ -0,0 +1,278 @@
+/*++ @file
+
+  Copyright ©2021 Liu Yi, liuyi28@lenovo.com
+
+  This program is just made available under the terms and conditions of the
+  MIT license: http://www.efikarl.com/mit-license.html
+
+  THE PROGRAM IS DISTRIBUTED UNDER THE MIT LICENSE ON AN "AS IS" BASIS,
+  WITHOUT WARRANTIES OR REPRESENTATIONS OF ANY KIND, EITHER EXPRESS OR IMPLIED.
+--*/
+
+use std::fs;
+use structopt::StructOpt;
+use path_slash::PathBufExt;
+
+use crate::audk;
+use crate::subcmd_ovrd;
+use crate::libs::cif;
+use crate::libs::ffs;
+use crate::libs::git;
+
+#[derive(StructOpt, Debug)]
+pub struct Convert {
+    /// Apply every override of cif onto codebase in place, and remove its cif entries
+    #[structopt(long, required_unless = "to-override", conflicts_with = "to-override")]
+    pub to_inplace      : bool,
+    /// Commit which edits codebase in place, to be turned into overrides with originals restored
+    #[structopt(long, value_name = "commit")]
+    pub to_override     : Option<String>,
+    /// Message of the commit left by convert
+    #[structopt(short, long)]
+    pub message         : Option<String>,
+}
+
+impl Convert {
+    pub fn handler(&self, layers: &audk::Layers) {
+        let top = layers.top().expect("ERR: oemovrd is neither given in cmdline or json");
+        let cif = top.cif.as_ref().expect("ERR: cif is None in json.");
+        if !cif.is_file() {
+            println!("ERR: want override.cif, but not a file: {:?}", cif);
+            return;
+        }
+        let dst = top.dst.as_ref().expect("ERR: dst is None in json.");
+        if !dst.is_dir() {
+            println!("ERR: want override.dst, but not a dir: {:?}", dst);
+            return;
+        }
+        // the commit left by convert has nothing else
+        match git::status_porcelain() {
+            Some(status) if status.is_empty() => {},
+            Some(_) => {
+                println!("ERR: workspace is dirty, commit or stash changes before convert");
+                return;
+            },
+            None => {
+                println!("ERR: workspace is not a git repository");
+                return;
+            },
+        }
+
+        let (converted, message) = match self.to_override.as_ref() {
+            Some(commit) => {
+                let commit = match git::rev_parse(commit) {
+                    Some(commit) => commit,
+                    None => {
+                        println!("ERR: invalid commit: {}", commit);
+                        return;
+                    }
+                };
+                let converted = self.to_override(&commit, cif, dst, &top.org, layers);
+                (converted, format!("Convert in-place changes of {} to overrides", &commit[..commit.len().min(12)]))
+            },
+            None => (self.to_inplace(cif, dst, &top.org, layers), format!("Convert overrides of {} to in-place changes", top.name)),
+        };
+        if converted == 0 {
+            println!("WRN: nothing is converted");
+            return;
+        }
+        let message = self.message.clone().unwrap_or(message);
+        if git::commit_all(&message) {
+            println!("INF: {} files are converted, and committed: {}", converted, message);
+        }
+    }
+
+    fn to_inplace(&self, cif: &std::path::PathBuf, dst: &std::path::PathBuf, org: &Option<std::path::PathBuf>, layers: &audk::Layers) -> usize {
+        // files overridden by lower layers too, whose overrides would win over codebase in place
+        let lower_srcs = layers.lower().iter().filter_map(|layer| layer.cif.as_ref())
+            .flat_map(|cif| cif::entries(cif)).map(|entry| entry.src.to_slash().unwrap()).collect::<Vec<_>>();
+        let mut converted = 0;
+        for entry in cif::entries(cif) {
+            let fdst = dst.join(&entry.src);
+            if !fdst.is_file() {
+                println!("WRN: override is not in {:?}, skip it: {}", dst, entry.src.to_slash().unwrap());
+                continue;
+            }
+            let fsrc = entry.src.to_slash().unwrap();
+            if lower_srcs.iter().any(|src| src.eq_ignore_ascii_case(&fsrc)) {
+                println!("WRN: overridden by lower layers too, keep it as an override: {}", fsrc);
+                continue;
+            }
+            // [0]: apply override onto codebase
+            if let Some(parent) = entry.src.parent() {
+                fs::create_dir_all(parent).unwrap();
+            }
+            ffs::copy(&fdst, &entry.src).unwrap();
+            // [1]: remove override, its original and cif entry
+            subcmd_ovrd::Ovrd::new(&entry.src, true, false).override_del(cif, dst, org);
+            println!("INF: in place {}", entry.src.to_slash().unwrap());
+            converted += 1;
+        }
+        converted
+    }
+
+    fn to_override(&self, commit: &str, cif: &std::path::PathBuf, dst: &std::path::PathBuf, org: &Option<std::path::PathBuf>, layers: &audk::Layers) -> usize {
+        let lower_dsts = layers.lower_dsts();
+        // changes of overrides themselves are not in place
+        let mut excludes = layers.0.iter().filter_map(|layer| layer.dst.as_ref()).map(Some).collect::<Vec<_>>();
+        excludes.push(org.as_ref());
+        let show_files = excludes.iter().collect::<Vec<_>>();
+        let output = git::show_no_format(&String::from(commit), false, "100%", "MADR", &show_files, true);
+        if !output.status.success() {
+            println!("convert.to_override: {:#?}", output);
+            return 0;
+        }
+        let parent = String::from(commit) + "~1";
+        let mut converted = 0;
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            let mut part = line.split('\t');
+            let (kind, old) = match (part.next(), part.next()) {
+                (Some(kind), Some(old)) => (kind, std::path::PathBuf::from_slash(old)),
+                _ => continue,
+            };
+            let new = part.next().map(std::path::PathBuf::from_slash);
+            if old.extension().map(|e| e == "cif" || e == "sdl").unwrap_or(false) {
+                println!("WRN: cif or sdl can not be overridden, keep it in place: {}", old.to_slash().unwrap());
+                continue;
+            }
+            // what is in workspace is taken as what the commit made
+            if let Some(file) = std::iter::once(&old).chain(new.as_ref()).find(|file| !unchanged_since(commit, file)) {
+                println!("WRN: changed since {}, keep it in place: {}", &commit[..commit.len().min(12)], file.to_slash().unwrap());
+                continue;
+            }
+            match kind.chars().next() {
+                Some('M') if self.override_modified(&old, commit, cif, dst, org, &lower_dsts) => {
+                    converted += 1;
+                },
+                Some('A') if self.override_added(&old, commit, cif, dst) => {
+                    converted += 1;
+                },
+                // deletion can not be an override, restore the original
+                Some('D') | Some('R') => {
+                    git::create_file_from(&parent, &old, &old, None);
+                    println!("WRN: deleted in place, which can not be an override, original is restored: {}", old.to_slash().unwrap());
+                    if let Some(new) = new.as_ref() {
+                        if self.override_added(new, commit, cif, dst) {
+                            converted += 1;
+                        }
+                    }
+                },
+                _ => {},
+            }
+        }
+        converted
+    }
+
+    fn override_modified(&self, fsrc: &std::path::PathBuf, commit: &str, cif: &std::path::PathBuf, dst: &std::path::PathBuf, org: &Option<std::path::PathBuf>, lower_dsts: &[&std::path::PathBuf]) -> bool {
+        let fdst = dst.join(fsrc);
+        if fdst.is_file() {
+            println!("WRN: overridden already, keep it in place: {}", fsrc.to_slash().unwrap());
+            return false;
+        }
+        let parent = String::from(commit) + "~1";
+        let (original, modified) = match (git::show_file(&parent, fsrc), git::show_file(commit, fsrc)) {
+            (Some(original), Some(modified)) => (original, modified),
+            _ => {
+                println!("WRN: not in {} or its parent, keep it in place: {}", commit, fsrc.to_slash().unwrap());
+                return false;
+            }
+        };
+        // [0]: restore original, and override it
+        ffs::force_rw(fsrc).unwrap();
+        fs::write(fsrc, original).unwrap();
+        subcmd_ovrd::Ovrd::new(fsrc, false, false).override_add(cif, dst, org, lower_dsts);
+        // [1]: override is what it is in place
+        ffs::force_rw(&fdst).unwrap();
+        fs::write(&fdst, modified).unwrap();
+        println!("INF: override {}", fsrc.to_slash().unwrap());
+        true
+    }
+
+    fn override_added(&self, fsrc: &std::path::PathBuf, commit: &str, cif: &std::path::PathBuf, dst: &std::path::PathBuf) -> bool {
+        let fdst = dst.join(fsrc);
+        if fdst.is_file() {
+            println!("WRN: overridden already, keep it in place: {}", fsrc.to_slash().unwrap());
+            return false;
+        }
+        // [0]: added file is an override-only file, and not in codebase
+        git::create_file_from(&String::from(commit), fsrc, &fdst, None);
+        if fsrc.is_file() {
+            ffs::remove_file(fsrc).unwrap();
+        }
+        subcmd_ovrd::Ovrd::new(fsrc, false, true).cif_add(cif, dst);
+        println!("INF: override {} (override-only)", fsrc.to_slash().unwrap());
+        true
+    }
+}
+
+/// Whether the file of HEAD is the same as it is at the commit, including that it is in neither
+fn unchanged_since(commit: &str, fsrc: &std::path::PathBuf) -> bool {
+    commit == git::rev_parse("HEAD").unwrap_or_default() || git::show_file(commit, fsrc) == git::show_file("HEAD", fsrc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(path: &str, text: &str) {
+        let path = std::path::Path::new(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, text).unwrap();
+    }
+
+    fn read(path: &str) -> String {
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn to_inplace_applies_overrides_onto_codebase() {
+        // paths of cif, dst and org are relative to workspace, as fwto runs in it
+        let ws = std::env::temp_dir().join(format!("fwto-convert-{}", std::process::id()));
+        fs::create_dir_all(&ws).unwrap();
+        let cwd = std::env::current_dir().unwrap();
+        std::env::set_current_dir(&ws).unwrap();
+
+        write("MdePkg/a.c", "codebase a\n");
+        write("MdePkg/x.c", "codebase x\n");
+        write("Original/MdePkg/a.c", "codebase a\n");
+        write("Original/MdePkg/x.c", "codebase x\n");
+        write("PlatformPkg/OVERRIDE/MdePkg/a.c", "ours a\n");
+        write("PlatformPkg/OVERRIDE/MdePkg/x.c", "ours x\n");
+        write("PlatformPkg/P.cif", "<component>\r\n\"OVERRIDE/MdePkg/a.c\";\"MdePkg/a.c\"\r\n\"OVERRIDE/MdePkg/x.c\";\"MdePkg/x.c\"\r\n\"OVERRIDE/MdePkg/gone.c\";\"MdePkg/gone.c\"\r\n<endComponent>\r\n");
+        // x.c is overridden by a lower layer too
+        write("Rc/OVERRIDE/MdePkg/x.c", "rc x\n");
+        write("Rc/R.cif", "<component>\r\n\"OVERRIDE/MdePkg/x.c\";\"MdePkg/x.c\"\r\n<endComponent>\r\n");
+
+        let layer = |name: &str, cif: &str, dst: &str, org: Option<&str>| audk::Layer {
+            name: String::from(name), cif: Some(std::path::PathBuf::from(cif)), dst: Some(std::path::PathBuf::from(dst)), org: org.map(std::path::PathBuf::from),
+        };
+        let oemovrd = audk::StdOvrd { cif: None, dst: None, org: None };
+        let layers = audk::Layers::new(vec![
+            layer("rc", "Rc/R.cif", "Rc/OVERRIDE", None),
+            layer("project", "PlatformPkg/P.cif", "PlatformPkg/OVERRIDE", Some("Original")),
+        ], true, &oemovrd);
+        let top = layers.top().unwrap();
+        let convert = Convert { to_inplace: true, to_override: None, message: None };
+        let converted = convert.to_inplace(top.cif.as_ref().unwrap(), top.dst.as_ref().unwrap(), &top.org, &layers);
+
+        let result = std::panic::catch_unwind(|| {
+            assert_eq!(converted, 1);
+            // a.c is in place, and its override, original and cif entry are removed
+            assert_eq!(read("MdePkg/a.c"), "ours a\n");
+            assert!(!std::path::Path::new("PlatformPkg/OVERRIDE/MdePkg/a.c").exists());
+            assert!(!std::path::Path::new("Original/MdePkg/a.c").exists());
+            // x.c is kept as an override, as the one of rc would win over codebase
+            assert_eq!(read("MdePkg/x.c"), "codebase x\n");
+            assert_eq!(read("PlatformPkg/OVERRIDE/MdePkg/x.c"), "ours x\n");
+            assert_eq!(read("Original/MdePkg/x.c"), "codebase x\n");
+            // gone.c is not in dst, and its entry is left as it is
+            assert_eq!(read("PlatformPkg/P.cif"), "<component>\r\n\"OVERRIDE/MdePkg/x.c\";\"MdePkg/x.c\"\r\n\"OVERRIDE/MdePkg/gone.c\";\"MdePkg/gone.c\"\r\n<endComponent>\r\n");
+            assert_eq!(read("Rc/OVERRIDE/MdePkg/x.c"), "rc x\n");
+        });
+        std::env::set_current_dir(cwd).unwrap();
+        fs::remove_dir_all(&ws).unwrap();
+        if let Err(e) = result {
+            std::panic::resume_unwind(e);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+/*++ @file
+
+  Copyright ©2021 Liu Yi, liuyi28@lenovo.com
+
+  This program is just made available under the terms and conditions of the
+  MIT license: http://www.efikarl.com/mit-license.html
+
+  THE PROGRAM IS DISTRIBUTED UNDER THE MIT LICENSE ON AN "AS IS" BASIS,
+  WITHOUT WARRANTIES OR REPRESENTATIONS OF ANY KIND, EITHER EXPRESS OR IMPLIED.
+--*/
+
+use std::fs;
+use structopt::StructOpt;
+
+use crate::audk;
+use crate::libs::ffs;
+use crate::libs::git;
+use crate::libs::toolkit;
+
+#[derive(StructOpt, Debug)]
+pub struct Doctor {
+    /// Max number of read-only files to be listed
+    #[structopt(long, default_value = "10")]
+    pub max_list        : usize,
+}
+
+#[derive(Default)]
+struct Report {
+    pass                : usize,
+    warn                : usize,
+    fail                : usize,
+}
+
+impl Report {
+    fn pass(&mut self, what: String) {
+        self.pass += 1;
+        println!("PASS: {}", what);
+    }
+
+    fn warn(&mut self, what: String, fix: &str) {
+        self.warn += 1;
+        println!("WARN: {}", what);
+        println!("      fix: {}", fix);
+    }
+
+    fn fail(&mut self, what: String, fix: &str) {
+        self.fail += 1;
+        println!("FAIL: {}", what);
+        println!("      fix: {}", fix);
+    }
+}
+
+impl Doctor {
+    pub fn handler(&self, ws: &std::path::PathBuf, layers: &audk::Layers, cfg_aptio_v: &Option<&audk::AptioV>) {
+        let mut report = Report::default();
+
+        //
+        // git
+        //
+        match git::version() {
+            Some(version) => report.pass(format!("git is on PATH: {}", version)),
+            None          => report.fail("git is not on PATH".to_string(), "install git, and add it to PATH"),
+        }
+        //
+        // workspace
+        //
+        if ws.join("MdePkg").is_dir() {
+            report.pass(format!("workspace is valid: {:?}", ws));
+            std::env::set_current_dir(ws).unwrap();
+        } else {
+            report.fail(format!("invalid workspace, MdePkg is not in: {:?}", ws), "give workspace by -w or project.workspace in json");
+            return self.summary(&report);
+        }
+        match git::status_porcelain() {
+            Some(status) if status.is_empty() => report.pass("workspace is clean".to_string()),
+            Some(status) => report.warn(format!("workspace is dirty: {} changes", status.lines().count()), "commit or stash changes, cbup resets and cleans the workspace"),
+            None         => report.fail("workspace is not a git repository".to_string(), "run fwto in a git repository of codebase"),
+        }
+        //
+        // override layers
+        //
+        let top = match layers.top() {
+            Some(top) => top,
+            None => {
+                report.fail("oemovrd is neither given in cmdline or json".to_string(), "give it by -c/-d/-o, or oemovrd or layers in json");
+                return self.summary(&report);
+            }
+        };
+        for layer in layers.lower() {
+            if let Some(cif) = layer.cif.as_ref() {
+                self.check_cif(&format!("{}.cif", layer.name), cif, false, &mut report);
+            }
+            match layer.dst.as_ref() {
+                Some(dst) if dst.is_dir() => report.pass(format!("{}.dst is a dir: {:?}", layer.name, dst)),
+                Some(dst) => report.warn(format!("{}.dst is set but not a dir: {:?}", layer.name, dst), "fix its dst in json, or remove the layer if it is not in use"),
+                None      => report.warn(format!("{}.dst is None in json", layer.name), "fix its dst in json, or remove the layer if it is not in use"),
+            }
+        }
+        match top.cif.as_ref() {
+            Some(cif) => self.check_cif(&format!("{}.cif", top.name), cif, true, &mut report),
+            None      => report.fail(format!("{}.cif is neither given in cmdline or json", top.name), "give it by -c or cif of the highest layer in json"),
+        }
+        match top.dst.as_ref() {
+            Some(dst) => self.check_dir(&format!("{}.dst", top.name), dst, &mut report),
+            None      => report.fail(format!("{}.dst is neither given in cmdline or json", top.name), "give it by -d or dst of the highest layer in json"),
+        }
+        if let Some(org) = top.org.as_ref() {
+            self.check_dir(&format!("{}.org", top.name), org, &mut report);
+        }
+        //
+        // aptio_v
+        //
+        let cfg_aptio_v = match cfg_aptio_v {
+            Some(cfg_aptio_v) => cfg_aptio_v,
+            None => {
+                report.warn("aptio_v is not in json".to_string(), "add aptio_v in json, or give them by options of build");
+                return self.summary(&report);
+            }
+        };
+        match cfg_aptio_v.project.veb.as_ref() {
+            Some(veb) if veb.is_file() => report.pass(format!("veb is a file: {:?}", veb)),
+            Some(veb) => report.fail(format!("invalid project veb: {:?}", veb), "fix aptio_v.project.veb in json"),
+            None      => report.warn("veb is None in json".to_string(), "give it by -v of build or aptio_v.project.veb in json"),
+        }
+        let version = |kind: toolkit::Kind, path: &std::path::Path| kind.version(path).map(|v| format!(", version {}", v)).unwrap_or_default();
+        match cfg_aptio_v.toolkit.ewdk.as_ref() {
+            Some(ewdk) if toolkit::is_ewdk(ewdk) => report.pass(format!("LaunchBuildEnv.cmd is in ewdk: {:?}{}", ewdk, version(toolkit::Kind::Ewdk, ewdk))),
+            Some(ewdk) => report.fail(format!("LaunchBuildEnv.cmd is not in ewdk: {:?}", ewdk), "fix aptio_v.toolkit.ewdk in json, to where LaunchBuildEnv.cmd is, or fwto init --toolkit"),
+            None       => report.warn("ewdk is None in json".to_string(), "give it by -e of build or aptio_v.toolkit.ewdk in json, or fwto init --toolkit"),
+        }
+        match cfg_aptio_v.toolkit.tools.as_ref() {
+            Some(tools) if toolkit::is_tools(tools) => report.pass(format!("make.exe and Bin are in tools: {:?}{}", tools, version(toolkit::Kind::Tools, tools))),
+            Some(tools) => report.fail(format!("make.exe or Bin is not in tools: {:?}", tools), "fix aptio_v.toolkit.tools in json, to BuildTools of Aptio_x.x_TOOLS_xx, or fwto init --toolkit"),
+            None        => report.warn("tools is None in json".to_string(), "give it by -t of build or aptio_v.toolkit.tools in json, or fwto init --toolkit"),
+        }
+        match cfg_aptio_v.toolkit.pycmd.as_ref() {
+            Some(pycmd) if toolkit::is_pycmd(pycmd) => report.pass(format!("python is a file: {:?}{}", pycmd, version(toolkit::Kind::Pycmd, pycmd))),
+            Some(pycmd) => report.fail(format!("invalid pycmd: {:?}", pycmd), "fix aptio_v.toolkit.pycmd in json, to the path of python.exe, python3 or python, or fwto init --toolkit"),
+            None        => report.warn("pycmd is None in json".to_string(), "give it by -p of build or aptio_v.toolkit.pycmd in json, or fwto init --toolkit"),
+        }
+
+        self.summary(&report);
+    }
+
+    fn check_cif(&self, what: &str, cif: &std::path::PathBuf, writable: bool, report: &mut Report) {
+        if !cif.is_file() {
+            return report.fail(format!("{} is not a file: {:?}", what, cif), "fix the cif path in cmdline or json");
+        }
+        if fs::read(cif).is_err() {
+            return report.fail(format!("{} is not readable: {:?}", what, cif), "check the permission of cif");
+        }
+        if !writable {
+            return report.pass(format!("{} is readable: {:?}", what, cif));
+        }
+        if ffs::is_readonly(cif) || fs::OpenOptions::new().append(true).open(cif).is_err() {
+            return report.fail(format!("{} is not writable: {:?}", what, cif), "clear the read-only attribute of cif, e.g. attrib -r");
+        }
+        report.pass(format!("{} is readable and writable: {:?}", what, cif));
+    }
+
+    fn check_dir(&self, what: &str, dir: &std::path::PathBuf, report: &mut Report) {
+        if !dir.is_dir() {
+            return report.fail(format!("{} is not a dir: {:?}", what, dir), "fix the dir path in cmdline or json, or create it");
+        }
+        if fs::read_dir(dir).is_err() {
+            return report.fail(format!("{} is not readable: {:?}", what, dir), "check the permission of dir");
+        }
+        // by metadata, nothing is written into the codebase by a diagnosis
+        if ffs::is_readonly(dir) {
+            return report.fail(format!("{} is not writable: {:?}", what, dir), "check the permission of dir");
+        }
+        report.pass(format!("{} is readable and writable: {:?}", what, dir));
+
+        let mut readonly = Vec::new();
+        ffs::walk(dir, &mut |path, is_dir| {
+            if !is_dir && ffs::is_readonly(path) {
+                readonly.push(path.to_path_buf());
+            }
+            is_dir
+        });
+        if readonly.is_empty() {
+            report.pass(format!("no read-only file in {}", what));
+        } else {
+            report.warn(format!("{} read-only files in {}, they will be forced to writable", readonly.len(), what), "clear the read-only attribute of files, e.g. attrib -r /s");
+            for path in readonly.iter().take(self.max_list) {
+                println!("      {:?}", path);
+            }
+        }
+    }
+
+    fn summary(&self, report: &Report) {
+        println!("---------------------------");
+        println!("INF: {} passed, {} warned, {} failed", report.pass, report.warn, report.fail);
+    }
+}
@@ -0,0 +1,265 @@
+/*++ @file
+
+  Copyright ©2021 Liu Yi, liuyi28@lenovo.com
+
+  This program is just made available under the terms and conditions of the
+  MIT license: http://www.efikarl.com/mit-license.html
+
+  THE PROGRAM IS DISTRIBUTED UNDER THE MIT LICENSE ON AN "AS IS" BASIS,
+  WITHOUT WARRANTIES OR REPRESENTATIONS OF ANY KIND, EITHER EXPRESS OR IMPLIED.
+--*/
+
+use std::fs;
+use std::io::prelude::*;
+use structopt::StructOpt;
+use path_slash::PathExt;
+
+use crate::audk;
+use crate::libs::cif;
+use crate::libs::ffs;
+use crate::libs::toolkit;
+
+#[derive(StructOpt, Debug)]
+pub struct Init {
+    /// Take the default of every choice, and write without confirm
+    #[structopt(short, long)]
+    pub yes             : bool,
+    /// Overwrite the configuration if it is already there
+    #[structopt(long)]
+    pub force           : bool,
+    /// Only discover toolkits, and fill aptio_v.toolkit of the configuration
+    #[structopt(long, conflicts_with = "force")]
+    pub toolkit         : bool,
+}
+
+/// A cif with override statements, and the dir where its overrides are in
+struct CifCandidate {
+    cif                 : std::path::PathBuf,
+    dst                 : std::path::PathBuf,
+    num                 : usize,
+}
+
+impl Init {
+    pub fn handler(&self, opt_project: &audk::Project, audk_json: &Option<String>) {
+        if self.toolkit {
+            return self.fill_toolkit(audk_json);
+        }
+        let ws = if let Some(ws) = &opt_project.workspace {
+            std::env::current_dir().unwrap().join(ws)
+        } else {
+            std::env::current_dir().unwrap()
+        };
+        if !ws.join("MdePkg").is_dir() {
+            println!("ERR: invalid workspace {:?}", ws);
+            return;
+        }
+
+        let conf = audk::Json::name(audk_json);
+        let fcfg = audk::Json::home().join(&conf);
+        if fcfg.is_file() && !self.force {
+            println!("ERR: configuration is already there: {:?}, use --force to overwrite", fcfg);
+            return;
+        }
+
+        println!("INF: scanning workspace {:?}", ws);
+        let (vebs, cifs, orgs) = scan_workspace(&ws);
+        println!("INF: scanning toolkits");
+        let found = toolkit::discover(&None);
+
+        let rel = |path: &std::path::Path| path.strip_prefix(&ws).unwrap().to_slash().unwrap();
+        let abs = |path: &std::path::Path| path.to_slash().unwrap();
+        let cif_names = cifs.iter().map(|c| format!("{} ({} overrides in {})", rel(&c.cif), c.num, rel(&c.dst))).collect::<Vec<_>>();
+
+        let mut json = serde_json::json!({
+            "project": {
+                "workspace": abs(&ws)
+            }
+        });
+        let oemovrd = self.choose("oemovrd.cif", &cif_names, Some(0));
+        let ibvovrd = if cifs.len() > 1 { self.choose("ibvovrd.cif", &cif_names, None) } else { None };
+        if let Some(i) = ibvovrd.filter(|i| Some(*i) != oemovrd) {
+            json["ibvovrd"] = serde_json::json!({
+                "cif": rel(&cifs[i].cif),
+                "dst": rel(&cifs[i].dst),
+            });
+        }
+        if let Some(i) = oemovrd {
+            json["oemovrd"] = serde_json::json!({
+                "cif": rel(&cifs[i].cif),
+                "dst": rel(&cifs[i].dst),
+            });
+            let org_names = orgs.iter().map(|o| rel(o)).collect::<Vec<_>>();
+            if let Some(o) = self.choose("oemovrd.org", &org_names, Some(0)) {
+                json["oemovrd"]["org"] = serde_json::json!(org_names[o]);
+            }
+        } else {
+            println!("WRN: no cif with override statements is found");
+        }
+
+        let mut aptio_v = serde_json::json!({
+            "project": {},
+            "toolkit": {},
+        });
+        let veb_names = vebs.iter().map(|v| rel(v)).collect::<Vec<_>>();
+        if let Some(i) = self.choose("aptio_v.project.veb", &veb_names, Some(0)) {
+            aptio_v["project"]["veb"] = serde_json::json!(veb_names[i]);
+        }
+        for kind in toolkit::KINDS {
+            let candidates = found.of(kind);
+            let names = candidates.iter().map(|c| c.name()).collect::<Vec<_>>();
+            if let Some(i) = self.choose(&format!("aptio_v.toolkit.{}", kind.key()), &names, Some(0)) {
+                aptio_v["toolkit"][kind.key()] = serde_json::json!(abs(&candidates[i].path));
+            }
+        }
+        json["aptio_v"] = aptio_v;
+
+        let text = serde_json::to_string_pretty(&json).unwrap();
+        println!("---------------------------");
+        println!("{}", text);
+        println!("---------------------------");
+        if !self.yes && !confirm(&format!("write configuration to {:?}?", fcfg)) {
+            println!("INF: configuration is not written");
+            return;
+        }
+        fs::write(&fcfg, text + "\n").unwrap();
+        audk::Json::select(&conf);
+        println!("INF: configuration is written: {:?}", fcfg);
+    }
+
+    /// Fill aptio_v.toolkit of the configuration by toolkits discovered, and keep the rest as it is
+    fn fill_toolkit(&self, audk_json: &Option<String>) {
+        let conf = audk::Json::name(audk_json);
+        let fcfg = audk::Json::home().join(&conf);
+        let mut json: serde_json::Value = match fs::read(&fcfg).ok().and_then(|buf| serde_json::from_slice(&buf).ok()) {
+            Some(json @ serde_json::Value::Object(_)) => json,
+            _ => {
+                println!("ERR: want configuration, but not a valid json: {:?}, run fwto init first", fcfg);
+                return;
+            }
+        };
+        let roots = serde_json::from_value(json["build"]["toolkit_roots"].clone()).ok();
+        println!("INF: scanning toolkits");
+        let found = toolkit::discover(&roots);
+        for kind in toolkit::KINDS {
+            let mut candidates = found.of(kind).to_vec();
+            // the one in json is the default if it is still valid
+            let current = json["aptio_v"]["toolkit"][kind.key()].as_str().map(std::path::PathBuf::from);
+            let default = match current.filter(|current| kind.is_valid(current)) {
+                Some(current) => match candidates.iter().position(|c| c.path == current) {
+                    Some(i) => i,
+                    None => {
+                        candidates.insert(0, toolkit::Candidate::new(kind, current));
+                        0
+                    }
+                },
+                None => 0,
+            };
+            let names = candidates.iter().map(|c| c.name()).collect::<Vec<_>>();
+            if let Some(i) = self.choose(&format!("aptio_v.toolkit.{}", kind.key()), &names, Some(default)) {
+                json["aptio_v"]["toolkit"][kind.key()] = serde_json::json!(candidates[i].path.to_slash().unwrap());
+            }
+        }
+
+        let text = serde_json::to_string_pretty(&json).unwrap();
+        println!("---------------------------");
+        println!("{}", serde_json::to_string_pretty(&json["aptio_v"]["toolkit"]).unwrap());
+        println!("---------------------------");
+        if !self.yes && !confirm(&format!("write toolkit to {:?}?", fcfg)) {
+            println!("INF: configuration is not written");
+            return;
+        }
+        fs::write(&fcfg, text + "\n").unwrap();
+        println!("INF: toolkit is written: {:?}", fcfg);
+    }
+
+    fn choose(&self, what: &str, candidates: &[String], default: Option<usize>) -> Option<usize> {
+        if candidates.is_empty() {
+            println!("WRN: no candidate of {}", what);
+            return None;
+        }
+        if self.yes || (candidates.len() == 1 && default == Some(0)) {
+            if let Some(i) = default {
+                println!("INF: {} = {}", what, candidates[i]);
+            }
+            return default;
+        }
+        println!("INF: candidates of {}:", what);
+        println!("    [0] none");
+        for (i, candidate) in candidates.iter().enumerate() {
+            println!("    [{}] {}", i + 1, candidate);
+        }
+        let default_no = default.map(|i| i + 1).unwrap_or(0);
+        loop {
+            print!("choose [{}]: ", default_no);
+            std::io::stdout().flush().unwrap();
+            let mut input = String::new();
+            if std::io::stdin().read_line(&mut input).unwrap() == 0 {
+                return default;
+            }
+            let input = input.trim();
+            if input.is_empty() {
+                return default;
+            }
+            match input.parse::<usize>() {
+                Ok(0) => return None,
+                Ok(n) if n <= candidates.len() => return Some(n - 1),
+                _ => println!("WRN: invalid choice: {}", input),
+            }
+        }
+    }
+}
+
+fn confirm(what: &str) -> bool {
+    print!("{} [Y/n]: ", what);
+    std::io::stdout().flush().unwrap();
+    let mut input = String::new();
+    std::io::stdin().read_line(&mut input).unwrap();
+    let input = input.trim().to_ascii_lowercase();
+    input.is_empty() || input == "y" || input == "yes"
+}
+
+fn scan_workspace(ws: &std::path::Path) -> (Vec<std::path::PathBuf>, Vec<CifCandidate>, Vec<std::path::PathBuf>) {
+    let mut vebs = Vec::new();
+    let mut cifs = Vec::new();
+    let mut orgs = Vec::new();
+    ffs::walk(ws, &mut |path, is_dir| {
+        let name = path.file_name().unwrap().to_string_lossy();
+        if is_dir {
+            if name.starts_with('.') || name == "Build" || name == audk::FWTO_WS {
+                return false;
+            }
+            if name.eq_ignore_ascii_case("Original") {
+                orgs.push(path.to_path_buf());
+                return false;
+            }
+            return true;
+        }
+        match path.extension().map(|e| e.to_ascii_lowercase()) {
+            Some(e) if e == "veb" => vebs.push(path.to_path_buf()),
+            Some(e) if e == "cif" => {
+                // the most common top dir of override statements is where overrides are in
+                let mut dsts: Vec<(std::path::PathBuf, usize)> = Vec::new();
+                for entry in cif::entries(path) {
+                    if let Some(top) = entry.dst.components().next() {
+                        let dst = path.parent().unwrap().join(top);
+                        if !dst.is_dir() {
+                            continue;
+                        }
+                        match dsts.iter_mut().find(|(d, _)| *d == dst) {
+                            Some((_, n)) => *n += 1,
+                            None => dsts.push((dst, 1)),
+                        }
+                    }
+                }
+                if let Some((dst, num)) = dsts.into_iter().max_by_key(|(_, n)| *n) {
+                    cifs.push(CifCandidate { cif: path.to_path_buf(), dst, num });
+                }
+            },
+            _ => {},
+        }
+        false
+    });
+    // the project cif has most overrides in general
+    cifs.sort_by_key(|c| std::cmp::Reverse(c.num));
+    (vebs, cifs, orgs)
+}
@@ -12,10 +12,14 @@
 use std::fs;
 use std::io::prelude::*;
 use std::result::Result;
+use std::collections::BTreeMap;
 use structopt::StructOpt;
+use path_slash::PathBufExt;
 
 use crate::audk;
 use crate::libs::ffs;
+use crate::libs::hook;
+use crate::libs::inf;
 
 #[derive(StructOpt, Debug)]
 pub struct Ovrd {
@@ -34,16 +38,22 @@ pub struct OvrdFlags {
     /// Skip original of override
     #[structopt(long)]
     pub skip_org            : bool,
+    /// Override src as a module: the inf, its sources and includes
+    #[structopt(long)]
+    pub module              : bool,
 }
 
+/// Groups of module overrides, which are recorded beside cif as <cif>.modules.json, out of dst
+const OVRD_MODULES      : &str = "modules.json";
+
 impl Ovrd {
     pub fn new(src: &std::path::PathBuf, clean: bool, skip_org: bool) -> Self {
         Ovrd {
-            src: std::path::PathBuf::from(src), flags: OvrdFlags { clean, skip_org }
+            src: std::path::PathBuf::from(src), flags: OvrdFlags { clean, skip_org, module: false }
         }
     }
 
-    pub fn handler(&self, opt_oemovrd: &audk::StdOvrd, cfg_oemovrd: &Option<&audk::StdOvrd>, cfg_ibvovrd: &Option<&audk::StdOvrd>) {
+    pub fn handler(&self, layers: &audk::Layers, scripts: &Option<&audk::Scripts>) {
         let ws = std::env::current_dir().unwrap();
         // Skip *.cif, *.sdl
         if self.src.extension().unwrap() == "cif" ||
@@ -55,58 +65,123 @@ impl Ovrd {
             println!("ERR: want override.src, but not a file: {:?}", self.src);
             return;
         }
-        let cif = if let Some(cif) = &opt_oemovrd.cif {
-            cif
-        } else {
-            cfg_oemovrd.expect("ERR: oemovrd is neither given in cmdline or json").cif.as_ref().expect("ERR: cif is None in json.")
-        };
+        let top = layers.top().expect("ERR: oemovrd is neither given in cmdline or json");
+        let cif = top.cif.as_ref().expect("ERR: cif is None in json.");
         if !cif.is_file() {
             println!("ERR: want override.cif, but not a file: {:?}", cif);
             return;
         }
-        let dst = if let Some(dst) = &opt_oemovrd.dst {
-            dst
-        } else {
-            cfg_oemovrd.expect("ERR: oemovrd is neither given in cmdline or json").dst.as_ref().expect("ERR: dst is None in json.")
-        };
+        let dst = top.dst.as_ref().expect("ERR: dst is None in json.");
         if !dst.is_dir() {
             println!("ERR: want override.dst, but not a dir: {:?}", dst);
             return;
         }
-        let org = if opt_oemovrd.org.is_some() {
-            &opt_oemovrd.org
+        let org = &top.org;
+
+        if self.flags.module && !self.src.extension().map(|e| e.eq_ignore_ascii_case("inf")).unwrap_or(false) {
+            println!("ERR: want override.src as module, but not an inf: {:?}", self.src);
+            return;
+        }
+        // files of the module, or the file itself
+        let files = if self.flags.module {
+            inf::module_files(&self.src).into_iter().filter(|f| f.is_file() && !f.extension().map(|e| e == "cif" || e == "sdl").unwrap_or(false)).collect()
         } else {
-            &cfg_oemovrd.expect("ERR: oemovrd is neither given in cmdline or json").org
+            vec![self.src.clone()]
         };
-
-        if self.flags.clean {
+        if !hook::run_stage(scripts, "pre_ovrd", &files) {
+            println!("ERR: ovrd is vetoed by pre_ovrd hook");
+            return;
+        }
+        if self.flags.module {
+            if self.flags.clean {
+                self.module_del(cif, dst, org);
+            } else {
+                self.module_add(cif, dst, org, &layers.lower_dsts());
+            }
+        } else if self.flags.clean {
             self.override_del(cif, dst, org);
         } else {
-            let ibvovrd_dst = if let Some(ibvovrd) = cfg_ibvovrd.as_deref() {
-                &ibvovrd.dst
-            } else {
-                &None
-            };
-            self.override_add(cif, dst, org, &ibvovrd_dst.as_ref());
+            self.override_add(cif, dst, org, &layers.lower_dsts());
+        }
+        hook::run_stage(scripts, "post_ovrd", &files);
+    }
+
+    fn modules_get(cif: &std::path::Path) -> BTreeMap<String, Vec<String>> {
+        let fmod = cif.with_extension(OVRD_MODULES);
+        if fmod.is_file() {
+            serde_json::from_slice(&fs::read(&fmod).unwrap()).expect("ERR: invalid format of modules")
+        } else {
+            BTreeMap::new()
         }
     }
 
+    fn modules_set(cif: &std::path::Path, modules: &BTreeMap<String, Vec<String>>) {
+        let fmod = cif.with_extension(OVRD_MODULES);
+        if modules.is_empty() {
+            if fmod.is_file() {
+                ffs::remove_file(&fmod).unwrap();
+            }
+        } else {
+            ffs::force_rw(&fmod).unwrap();
+            fs::write(&fmod, serde_json::to_string_pretty(modules).unwrap() + "\n").unwrap();
+        }
+    }
+
+    pub fn module_add(&self, cif: &std::path::PathBuf, dst: &std::path::PathBuf, org: &Option<std::path::PathBuf>, lower_dsts: &[&std::path::PathBuf]) {
+        let inf = ffs::normalize(&self.src).to_slash().unwrap();
+        let mut modules = Self::modules_get(cif);
+        let group = modules.entry(inf).or_default();
+        for file in inf::module_files(&self.src) {
+            if !file.is_file() {
+                println!("WRN: file of module is not found: {:?}", file);
+                continue;
+            }
+            if file.extension().map(|e| e == "cif" || e == "sdl").unwrap_or(false) {
+                continue;
+            }
+            let name = file.to_slash().unwrap();
+            println!("INF: override {}", name);
+            // only overrides there are in the group
+            if Ovrd::new(&file, false, self.flags.skip_org).override_add(cif, dst, org, lower_dsts) && !group.contains(&name) {
+                group.push(name);
+            }
+        }
+        modules.retain(|_, group| !group.is_empty());
+        Self::modules_set(cif, &modules);
+    }
+
+    pub fn module_del(&self, cif: &std::path::PathBuf, dst: &std::path::PathBuf, org: &Option<std::path::PathBuf>) {
+        let inf = ffs::normalize(&self.src).to_slash().unwrap();
+        let mut modules = Self::modules_get(cif);
+        let group = match modules.remove(&inf) {
+            Some(group) => group,
+            None => {
+                println!("WRN: module is not recorded, clean files of module from inf: {:?}", self.src);
+                inf::module_files(&self.src).iter()
+                    .filter(|f| !f.extension().map(|e| e == "cif" || e == "sdl").unwrap_or(false))
+                    .map(|f| f.to_slash().unwrap()).collect()
+            }
+        };
+        for name in group {
+            println!("INF: clean {}", name);
+            Ovrd::new(&std::path::PathBuf::from_slash(name), true, self.flags.skip_org).override_del(cif, dst, org);
+        }
+        Self::modules_set(cif, &modules);
+    }
+
     fn build_cif_override_line(&self, dst: &std::path::PathBuf) -> String {
         let ws       = std::env::current_dir().unwrap();
         let ovrd_tmp = std::env::current_dir().unwrap().join(&self.src);
         //  support relative to current file
         let ovrd_src = ovrd_tmp.strip_prefix(&ws).unwrap();
         let ovrd_dst = std::path::PathBuf::from(dst.file_name().unwrap()).join(&ovrd_src);
-        // +path_slash
-        use path_slash::PathBufExt;
-        // -path_slash
         let ovrd_src = std::path::PathBuf::from_slash(ovrd_src.to_str().unwrap());
         let ovrd_dst = std::path::PathBuf::from_slash(ovrd_dst.to_str().unwrap());
 
         String::new() + r#"""# + ovrd_dst.to_str().unwrap() + r#"";""# + ovrd_src.to_str().unwrap() + r#"""# + "\r\n"
     }
 
-    fn add_override_files(&self, dst: &std::path::PathBuf, org: &Option<std::path::PathBuf>, ibvovrd_dst: &Option<&std::path::PathBuf>) -> Result<bool, String> {
+    fn add_override_files(&self, dst: &std::path::PathBuf, org: &Option<std::path::PathBuf>, lower_dsts: &[&std::path::PathBuf]) -> Result<bool, String> {
         let mut is_1st_time_ovrd = true;
 
         let fdst = std::path::PathBuf::from(dst).join(&self.src);
@@ -117,12 +192,9 @@ impl Ovrd {
                 fs::create_dir_all(fdst_parent).unwrap();
             }
             ffs::copy(&self.src, &fdst).unwrap();
-            // override if there is ibvovrd.fsrc
-            if let Some(ibvovrd_dst) = ibvovrd_dst {
-                let ibvovrd_fsrc = ibvovrd_dst.join(&self.src);
-                if  ibvovrd_fsrc.is_file() {
-                    ffs::copy(&ibvovrd_fsrc, &fdst).unwrap();
-                }
+            // override if there is fsrc in lower layers, the highest one wins
+            if let Some(lower_fsrc) = lower_dsts.iter().rev().map(|lower_dst| lower_dst.join(&self.src)).find(|f| f.is_file()) {
+                ffs::copy(&lower_fsrc, &fdst).unwrap();
             }
         } else {
             is_1st_time_ovrd = false;
@@ -161,16 +233,23 @@ impl Ovrd {
         }
     }
 
-    pub fn override_add(&self, cif: &std::path::PathBuf, dst: &std::path::PathBuf, org: &Option<std::path::PathBuf>, ibvovrd_dst: &Option<&std::path::PathBuf>) {
-        let result = self.add_override_files(dst, org, ibvovrd_dst);
+    /// Override src, true if it is overridden, or it was already
+    pub fn override_add(&self, cif: &std::path::PathBuf, dst: &std::path::PathBuf, org: &Option<std::path::PathBuf>, lower_dsts: &[&std::path::PathBuf]) -> bool {
+        let result = self.add_override_files(dst, org, lower_dsts);
         if let Err(error) = result {
-            return println!("{}", error)
+            println!("{}", error);
+            return false
         }
 
         if let Ok(is_1st_time_ovrd) = result {
-            if !is_1st_time_ovrd { return }
+            if !is_1st_time_ovrd { return true }
         }
-        // build [file] override statement and add to cif
+        self.cif_add(cif, dst);
+        true
+    }
+
+    /// Add [file] override statement to cif, ahead of <endComponent>
+    pub fn cif_add(&self, cif: &std::path::PathBuf, dst: &std::path::PathBuf) {
         let cif_override_line = self.build_cif_override_line(dst);
 
         let cifbf = fs::read_to_string(cif).unwrap();
@@ -205,7 +284,7 @@ impl Ovrd {
         }
     }
 
-    pub fn override_replace_with(&self, new: &Self, cif: &std::path::PathBuf, dst: &std::path::PathBuf, org: &Option<std::path::PathBuf>, ibvovrd_dst: &Option<&std::path::PathBuf>) {
+    pub fn override_replace_with(&self, new: &Self, cif: &std::path::PathBuf, dst: &std::path::PathBuf, org: &Option<std::path::PathBuf>, lower_dsts: &[&std::path::PathBuf]) {
         let old_dst_file = std::path::PathBuf::from(dst.file_name().unwrap()).join(&self.src);
 
         let cifbf = fs::read_to_string(cif).unwrap();
@@ -216,7 +295,7 @@ impl Ovrd {
             if let Some((_fdst, _fsrc)) = line.trim().split_once(";") {
                 if std::path::PathBuf::from(_fdst.trim_matches('"')) == old_dst_file {
                     // replace old override with new override
-                    let result = new.add_override_files(dst, org, ibvovrd_dst);
+                    let result = new.add_override_files(dst, org, lower_dsts);
                     if let Err(error) = result {
                         return println!("{}", error)
                     }
@@ -9,11 +9,15 @@
   WITHOUT WARRANTIES OR REPRESENTATIONS OF ANY KIND, EITHER EXPRESS OR IMPLIED.
 --*/
 
+use std::fs;
 use structopt::StructOpt;
 use path_slash::PathBufExt;
 
 use crate::audk;
 use crate::libs::git;
+use crate::libs::hook;
+use crate::libs::html;
+use crate::libs::tool;
 
 #[derive(StructOpt, Debug)]
 pub struct View {
@@ -23,62 +27,413 @@ pub struct View {
     /// Old commit for diff
     #[structopt(short, long, parse(from_str) )]
     pub old             : Option<String>,
+    /// Format of diffs: tree for compare tools, patch of unified diff, or html report
+    #[structopt(short, long, default_value = "tree", possible_values = &["tree", "patch", "html"])]
+    pub format          : String,
+    /// Mode of review: commit for changes between commits, or delta for changes of overrides against their originals
+    #[structopt(short, long, default_value = "commit", possible_values = &["commit", "delta"])]
+    pub mode            : String,
+    /// Open diff trees by difftool of json, every file one after another
+    #[structopt(long)]
+    pub tool            : bool,
+    /// With --tool, open the dir pair instead of every file
+    #[structopt(long)]
+    pub dir_diff        : bool,
+    /// Similarity threshold of renames, e.g. 75%
+    #[structopt(long, default_value = "100%", parse(try_from_str = git::similarity))]
+    pub find_renames    : String,
 }
 
 const VIEW_HOME         : &str = "view";
 const VIEW_OLD          : &str = "old";
 const VIEW_NEW          : &str = "new";
+const VIEW_PATCH        : &str = "view.patch";
+const VIEW_HTML         : &str = "view.html";
+const VIEW_NONOVRD      : &str = "non-override";
+const VIEW_DELTA        : &str = "delta";
+const VIEW_DELTA_PATCH  : &str = "view.delta";
+const VIEW_REVIEWED     : &str = "reviewed.log";
+
+/// A file of old commit paired with a file of new commit, to be compared
+struct Pair {
+    /// Non-override, or layer of the override
+    group               : String,
+    /// Where the pair is in diff trees
+    fsrc                : std::path::PathBuf,
+    old                 : (String, std::path::PathBuf),
+    new                 : (String, std::path::PathBuf),
+}
+
+impl Pair {
+    fn new(group: &str, fsrc: &std::path::Path, old: (&String, &std::path::Path), new: (&String, &std::path::Path)) -> Self {
+        Self {
+            group: String::from(group), fsrc: fsrc.to_path_buf(), old: (old.0.clone(), old.1.to_path_buf()), new: (new.0.clone(), new.1.to_path_buf())
+        }
+    }
+
+    fn old_label(&self, exists: bool) -> String {
+        if exists { String::from("a/") + &self.old.1.to_slash().unwrap() } else { String::from("/dev/null") }
+    }
+
+    fn new_label(&self, exists: bool) -> String {
+        if exists { String::from("b/") + &self.new.1.to_slash().unwrap() } else { String::from("/dev/null") }
+    }
+
+    fn patch_header(&self, old_exists: bool, new_exists: bool) -> String {
+        let mut header = format!("diff --git a/{} b/{}\n", self.old.1.to_slash().unwrap(), self.new.1.to_slash().unwrap());
+        if !old_exists {
+            header += "new file mode 100644\n";
+        }
+        if !new_exists {
+            header += "deleted file mode 100644\n";
+        }
+        header
+    }
+}
 
 impl View {
-    pub fn handler(&self, opt_oemovrd: &audk::StdOvrd, cfg_oemovrd: &Option<&audk::StdOvrd>, cfg_ibvovrd: &Option<&audk::StdOvrd>) {
-        let cif = if let Some(cif) = &opt_oemovrd.cif {
-            cif
-        } else {
-            cfg_oemovrd.expect("ERR: oemovrd is neither given in cmdline or json").cif.as_ref().expect("ERR: cif is None in json.")
-        };
+    pub fn handler(&self, layers: &audk::Layers, difftool: &Option<&audk::Tool>, scripts: &Option<&audk::Scripts>) {
+        let top = layers.top().expect("ERR: oemovrd is neither given in cmdline or json");
+        let cif = top.cif.as_ref().expect("ERR: cif is None in json.");
         if !cif.is_file() {
             println!("ERR: want override.cif, but not a file: {:?}", cif);
             return;
         }
 
-        let dst = if let Some(dst) = &opt_oemovrd.dst {
-            dst
-        } else {
-            cfg_oemovrd.expect("ERR: oemovrd is neither given in cmdline or json").dst.as_ref().expect("ERR: dst is None in json.")
-        };
+        let dst = top.dst.as_ref().expect("ERR: dst is None in json.");
         if !dst.is_dir() {
             println!("ERR: want override.dst, but not a dir: {:?}", dst);
             return;
         }
         let dst = &Some(dst);
 
-        let org = if opt_oemovrd.org.is_some() {
-            &opt_oemovrd.org
-        } else {
-            &cfg_oemovrd.expect("ERR: oemovrd is neither given in cmdline or json").org
-        };
-        let org = &org.as_ref();
-
-        let ibvovrd_dst = if let Some(ibvovrd) = cfg_ibvovrd.as_deref() {
-            &ibvovrd.dst
-        } else {
-            &None
-        };
-        let ibvovrd_dst = &ibvovrd_dst.as_ref();
+        if self.tool && self.mode != "delta" && self.format != "tree" {
+            println!("ERR: --tool works with diff trees, which are of --format tree or --mode delta");
+            return;
+        }
 
         let def = String::new() + &self.new + "~";
         let old = self.old.as_ref().unwrap_or(&def);
 
-        self.review_nonovrd(old, dst, org, ibvovrd_dst);
-        self.review_ibvovrd(old,           ibvovrd_dst);
-        self.review_oemovrd(old, dst);
+        let v_path = std::path::PathBuf::from(audk::FWTO_WS).join(VIEW_HOME);
+        if self.mode == "delta" {
+            let files = self.write_delta(old, layers);
+            self.launch_difftool(&v_path.join(VIEW_DELTA), difftool);
+            hook::run_stage(scripts, "post_view", &files);
+            return;
+        }
+
+        // override in patch is against its codebase original, rather than its previous version
+        let against_codebase = self.format == "patch";
+        let mut pairs = Vec::new();
+        self.review_nonovrd(old, layers, &mut pairs);
+        // walk lower layers in order, so diff trees of the higher layer win
+        for layer in layers.lower() {
+            self.review_lowerovrd(old, layer, against_codebase, &mut pairs);
+        }
+        self.review_oemovrd(old, &top.name, dst, against_codebase, &mut pairs);
+
+        match self.format.as_str() {
+            "patch" => self.write_patch(&pairs),
+            "html"  => self.write_html(old, layers, &pairs),
+            _       => self.write_tree(&pairs),
+        }
+        self.launch_difftool(&v_path, difftool);
+        let mut files = pairs.iter().map(|pair| pair.fsrc.clone()).collect::<Vec<_>>();
+        files.sort();
+        files.dedup();
+        hook::run_stage(scripts, "post_view", &files);
     }
 
-    fn review_nonovrd(&self, self_old: &String, dst: &Option<&std::path::PathBuf>, org: &Option<&std::path::PathBuf>, ibvovrd_dst: &Option<&std::path::PathBuf>) {
-        let find_renames = "100%";
-        let show_files   = vec![dst, org, ibvovrd_dst];
+    /// Open old and new trees of home by difftool, and record reviewed files
+    fn launch_difftool(&self, home: &std::path::Path, difftool: &Option<&audk::Tool>) {
+        if !self.tool {
+            return;
+        }
+        let tool = match difftool {
+            Some(tool) => tool,
+            None => {
+                println!("ERR: difftool is not in json");
+                return;
+            }
+        };
+        let old = home.join(VIEW_OLD);
+        let new = home.join(VIEW_NEW);
+        if self.dir_diff {
+            tool::launch(&tool.cmd, &tool.args, &[("local", &old), ("remote", &new)]);
+            return;
+        }
+        let mut files = tool::files_under(&old);
+        files.extend(tool::files_under(&new));
+        files.sort();
+        files.dedup();
+        let mut reviewed = Vec::new();
+        for file in &files {
+            println!("INF: reviewing {:?}", file);
+            match tool::launch(&tool.cmd, &tool.args, &[("local", &old.join(file)), ("remote", &new.join(file))]) {
+                Some(status) if status.success() || !tool.trust_exit_code.unwrap_or(true) => reviewed.push(file.clone()),
+                Some(_) => println!("WRN: {:?} is not reviewed", file),
+                None => break,
+            }
+        }
+        let log = home.join(VIEW_REVIEWED);
+        if !reviewed.is_empty() {
+            tool::record(&log, &reviewed);
+        }
+        println!("INF: {} of {} files are reviewed, see {:?}", reviewed.len(), files.len(), log);
+    }
+
+    fn write_tree(&self, pairs: &[Pair]) {
+        let v_path = std::path::PathBuf::from(audk::FWTO_WS).join(VIEW_HOME);
+        for pair in pairs {
+            let old = v_path.join(VIEW_OLD).join(&pair.fsrc);
+            let new = v_path.join(VIEW_NEW).join(&pair.fsrc);
+            // create old
+            git::create_file_from(&pair.old.0, &pair.old.1, &old, None);
+            // create new
+            git::create_file_from(&pair.new.0, &pair.new.1, &new, None);
+        }
+    }
+
+    /// Unified diff of the pair by git, and whether old and new exist
+    fn diff_pair(&self, pair: &Pair, t_path: &std::path::Path) -> Option<(bool, bool, String)> {
+        let old = git::show_file(&pair.old.0, &pair.old.1);
+        let new = git::show_file(&pair.new.0, &pair.new.1);
+        if old.is_none() && new.is_none() {
+            return None;
+        }
+        if !t_path.is_dir() {
+            fs::create_dir_all(t_path).unwrap();
+        }
+        let old_tmp = match &old {
+            Some(buf) => { let f = t_path.join(VIEW_OLD); fs::write(&f, buf).unwrap(); f },
+            None      => std::path::PathBuf::from("/dev/null"),
+        };
+        let new_tmp = match &new {
+            Some(buf) => { let f = t_path.join(VIEW_NEW); fs::write(&f, buf).unwrap(); f },
+            None      => std::path::PathBuf::from("/dev/null"),
+        };
+        match git::diff_no_index(&old_tmp, &new_tmp) {
+            Some(diff) if !diff.is_empty() => Some((old.is_some(), new.is_some(), diff)),
+            _ => None,
+        }
+    }
+
+    fn write_patch(&self, pairs: &[Pair]) {
+        let v_path = std::path::PathBuf::from(audk::FWTO_WS).join(VIEW_HOME);
+        let t_path = v_path.join(".patch");
+        let mut patch = String::new();
+        for pair in pairs {
+            let (old, new, diff) = match self.diff_pair(pair, &t_path) {
+                Some(diff) => diff,
+                None => continue,
+            };
+            let old_label = pair.old_label(old);
+            let new_label = pair.new_label(new);
+            patch += &pair.patch_header(old, new);
+            // keep index and hunks of git, but headers are of the pair
+            if let Some(index) = diff.split_inclusive('\n').find(|line| line.starts_with("index ")) {
+                patch += index;
+            }
+            let mut in_hunks = false;
+            for line in diff.split_inclusive('\n') {
+                if line.starts_with("Binary files ") {
+                    patch += &format!("Binary files {} and {} differ\n", old_label, new_label);
+                    break;
+                }
+                if line.starts_with("@@") && !in_hunks {
+                    in_hunks = true;
+                    patch += &format!("--- {}\n+++ {}\n", old_label, new_label);
+                }
+                if in_hunks {
+                    patch += line;
+                }
+            }
+        }
+        if t_path.is_dir() {
+            fs::remove_dir_all(&t_path).unwrap();
+        }
+        let fpatch = v_path.join(VIEW_PATCH);
+        fs::write(&fpatch, patch).unwrap();
+        println!("INF: patch is written: {:?}", fpatch);
+    }
 
+    fn write_html(&self, self_old: &str, layers: &audk::Layers, pairs: &[Pair]) {
         let v_path = std::path::PathBuf::from(audk::FWTO_WS).join(VIEW_HOME);
+        let t_path = v_path.join(".html");
+        let title = format!("fwto view: {}..{}", self_old, self.new);
+
+        // group in order of non-override and layers
+        let mut groups = vec![String::from(VIEW_NONOVRD)];
+        groups.extend(layers.0.iter().map(|layer| layer.name.clone()));
+        let mut files = Vec::new();
+        for pair in pairs {
+            if let Some((old, new, diff)) = self.diff_pair(pair, &t_path) {
+                let (rows, adds, dels) = html::side_by_side(&diff);
+                let rows = if rows.is_empty() { String::from(r#"<tr><td colspan="4">Binary files differ</td></tr>"#) } else { rows };
+                files.push((pair, pair.old_label(old), pair.new_label(new), rows, adds, dels));
+            }
+        }
+        if t_path.is_dir() {
+            fs::remove_dir_all(&t_path).unwrap();
+        }
+
+        let (adds, dels) = files.iter().fold((0, 0), |(a, d), f| (a + f.4, d + f.5));
+        let mut body = format!("<h1>{}</h1>\n<p>{} files changed, <span class=\"stat-add\">+{}</span> <span class=\"stat-del\">-{}</span></p>\n", html::escape(&title), files.len(), adds, dels);
+        for group in &groups {
+            let in_group = files.iter().enumerate().filter(|(_, f)| &f.0.group == group).collect::<Vec<_>>();
+            if in_group.is_empty() {
+                continue;
+            }
+            let group_name = if group == VIEW_NONOVRD { group.clone() } else { format!("{} override", group) };
+            body += &format!("<h2>{} ({})</h2>\n<ul>\n", html::escape(&group_name), in_group.len());
+            for (i, f) in &in_group {
+                body += &format!("<li><a href=\"#f{}\">{}</a> <span class=\"stat-add\">+{}</span> <span class=\"stat-del\">-{}</span></li>\n", i, html::escape(&f.0.fsrc.to_slash().unwrap()), f.4, f.5);
+            }
+            body += "</ul>\n";
+        }
+        for (i, f) in files.iter().enumerate() {
+            body += &format!("<div class=\"file\" id=\"f{}\">\n<h3>{} <span class=\"stat-add\">+{}</span> <span class=\"stat-del\">-{}</span></h3>\n", i, html::escape(&f.0.fsrc.to_slash().unwrap()), f.4, f.5);
+            body += &format!("<p class=\"labels\">{}:{} &rarr; {}:{}</p>\n", html::escape(&f.0.old.0), html::escape(&f.1), html::escape(&f.0.new.0), html::escape(&f.2));
+            body += &format!("<table class=\"diff\">\n{}</table>\n</div>\n", f.3);
+        }
+        let page = format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n", html::escape(&title), html::STYLE, body);
+
+        if !v_path.is_dir() {
+            fs::create_dir_all(&v_path).unwrap();
+        }
+        let fhtml = v_path.join(VIEW_HTML);
+        fs::write(&fhtml, page).unwrap();
+        println!("INF: html is written: {:?}", fhtml);
+    }
+
+    /// Delta of the override against its codebase original at the commit, which is None if the override is not there
+    fn delta_at(&self, commit: &String, fsrc: &std::path::Path, fdst: &std::path::PathBuf, t_path: &std::path::Path) -> Option<String> {
+        git::show_file(commit, fdst)?;
+        let pair = Pair::new(VIEW_DELTA, fsrc, (commit, fsrc), (commit, fdst));
+        let (old, _, diff) = match self.diff_pair(&pair, t_path) {
+            Some(diff) => diff,
+            None => return Some(String::new()),
+        };
+        let mut delta = format!("--- {}\n+++ {}\n", pair.old_label(old), pair.new_label(true));
+        let mut in_hunks = false;
+        for line in diff.split_inclusive('\n') {
+            if line.starts_with("Binary files ") {
+                delta += "Binary files differ\n";
+                break;
+            }
+            // hunks without line numbers, so lines moved by codebase are not taken as changes of delta
+            if line.starts_with("@@") {
+                in_hunks = true;
+                delta += &format!("@@{}", line.splitn(3, "@@").nth(2).unwrap_or("\n"));
+            } else if in_hunks {
+                delta += line;
+            }
+        }
+        Some(delta)
+    }
+
+    fn write_delta(&self, self_old: &String, layers: &audk::Layers) -> Vec<std::path::PathBuf> {
+        let d_path = std::path::PathBuf::from(audk::FWTO_WS).join(VIEW_HOME).join(VIEW_DELTA);
+        let t_path = d_path.join(".delta");
+        if d_path.is_dir() {
+            fs::remove_dir_all(&d_path).unwrap();
+        }
+        // originals changed by codebase change deltas too
+        let mut nonovrd = Vec::new();
+        self.review_nonovrd(self_old, layers, &mut nonovrd);
+
+        let mut patch = String::new();
+        let mut touched = Vec::new();
+        let mut changed = 0;
+        for layer in &layers.0 {
+            let dst = match layer.dst.as_ref() {
+                Some(dst) if dst.is_dir() => dst,
+                _ => continue,
+            };
+            let dst_       = Some(dst);
+            let show_files = vec![&dst_];
+            let output = git::diff_no_format(self_old, &self.new, true, "100%", "ADM", &show_files, false);
+            if !output.status.success() {
+                println!("write_delta: {:#?}", output);
+                continue;
+            }
+            let mut fsrcs = String::from_utf8(output.stdout).unwrap().lines()
+                .map(|line| std::path::PathBuf::from(std::path::PathBuf::from(line).strip_prefix(dst.to_slash().unwrap()).unwrap()))
+                .collect::<Vec<_>>();
+            for pair in &nonovrd {
+                let fdst = dst.join(&pair.fsrc);
+                if git::show_file(self_old, &fdst).is_some() || git::show_file(&self.new, &fdst).is_some() {
+                    fsrcs.push(pair.fsrc.clone());
+                }
+            }
+            fsrcs.sort();
+            fsrcs.dedup();
+
+            for fsrc in fsrcs {
+                touched.push(fsrc.clone());
+                let fdst = dst.join(&fsrc);
+                let fdiff = std::path::PathBuf::from(&layer.name).join(fsrc.to_slash().unwrap() + ".diff");
+                let old_delta = self.delta_at(self_old, &fsrc, &fdst, &t_path);
+                let new_delta = self.delta_at(&self.new, &fsrc, &fdst, &t_path);
+                // trees of deltas for compare tools, and diff of deltas for the patch
+                let mut deltas = Vec::new();
+                for (side, delta) in [(VIEW_OLD, &old_delta), (VIEW_NEW, &new_delta)] {
+                    let tmp = match delta {
+                        Some(delta) => {
+                            let f = d_path.join(side).join(&fdiff);
+                            fs::create_dir_all(f.parent().unwrap()).unwrap();
+                            fs::write(&f, delta).unwrap();
+                            f
+                        },
+                        None => std::path::PathBuf::from("/dev/null"),
+                    };
+                    deltas.push(tmp);
+                }
+                let diff = match git::diff_no_index(&deltas[0], &deltas[1]) {
+                    Some(diff) if !diff.is_empty() => diff,
+                    _ => continue,
+                };
+                changed += 1;
+                let old_label = if old_delta.is_some() { format!("a/{}", fdiff.to_slash().unwrap()) } else { String::from("/dev/null") };
+                let new_label = if new_delta.is_some() { format!("b/{}", fdiff.to_slash().unwrap()) } else { String::from("/dev/null") };
+                patch += &format!("diff --git a/{0} b/{0}\n", fdiff.to_slash().unwrap());
+                patch += &format!("--- {}\n+++ {}\n", old_label, new_label);
+                patch += &diff.split_inclusive('\n').skip_while(|line| !line.starts_with("@@")).collect::<String>();
+            }
+        }
+        if t_path.is_dir() {
+            fs::remove_dir_all(&t_path).unwrap();
+        }
+        if !d_path.is_dir() {
+            fs::create_dir_all(&d_path).unwrap();
+        }
+        let fpatch = d_path.join(VIEW_DELTA_PATCH);
+        fs::write(&fpatch, patch).unwrap();
+        println!("INF: {} overrides are touched, and {} deltas are changed", touched.len(), changed);
+        println!("INF: diff of deltas is written: {:?}", fpatch);
+        touched
+    }
+
+    /// Renames by --find-renames, as pairs of old and new paths
+    fn renames(&self, self_old: &String, show_files: &Vec<&Option<&std::path::PathBuf>>, exclude_show_files: bool) -> Vec<(std::path::PathBuf, std::path::PathBuf)> {
+        let output = git::diff_no_format(self_old, &self.new, false, &self.find_renames, "R", show_files, exclude_show_files);
+        if !output.status.success() {
+            println!("renames: {:#?}", output);
+            return Vec::new();
+        }
+        // e.g. R075<TAB>old<TAB>new
+        String::from_utf8_lossy(&output.stdout).lines().filter_map(|line| {
+            let mut part = line.split('\t').skip(1);
+            Some((std::path::PathBuf::from(part.next()?), std::path::PathBuf::from(part.next()?)))
+        }).collect()
+    }
+
+    fn review_nonovrd(&self, self_old: &String, layers: &audk::Layers, pairs: &mut Vec<Pair>) {
+        let find_renames = self.find_renames.as_str();
+        let ovrd_dirs    = layers.0.iter().flat_map(|layer| vec![layer.dst.as_ref(), layer.org.as_ref()]).collect::<Vec<_>>();
+        let show_files   = ovrd_dirs.iter().collect::<Vec<_>>();
+
         let output = git::diff_no_format(&self_old, &self.new, true, find_renames, "ADM", &show_files, true);
         if !output.status.success() {
             println!("codebase_oemovrd.d: {:#?}", output);
@@ -86,90 +441,88 @@ impl View {
             let diff_result = String::from_utf8(output.clone().stdout).unwrap();
             for line in diff_result.lines() {
                 let fsrc = std::path::PathBuf::from(line);
-                // [1]: diff trees for better compare
-                let old = v_path.join(VIEW_OLD).join(&fsrc);
-                let new = v_path.join(VIEW_NEW).join(&fsrc);
-                // create old
-                git::create_file_from(&self_old, &fsrc, &old, None);
-                // create new
-                git::create_file_from(&self.new, &fsrc, &new, None);
+                // [1]: pair for better compare
+                pairs.push(Pair::new(VIEW_NONOVRD, &fsrc, (&self_old, &fsrc), (&self.new, &fsrc)));
             }
         }
+        for (fold, fsrc) in self.renames(self_old, &show_files, true) {
+            // [1]: pair for better compare, in the place of the new name
+            pairs.push(Pair::new(VIEW_NONOVRD, &fsrc, (self_old, &fold), (&self.new, &fsrc)));
+        }
     }
 
-    fn review_ibvovrd(&self, self_old: &String, ibvovrd_dst: &Option<&std::path::PathBuf>) {
-        if let Some(ibvovrd_dst) = ibvovrd_dst {
-            if !ibvovrd_dst.is_dir() {
-                println!("WRN: ibvovrd.dst is set but not a dir: {:?}", &ibvovrd_dst);
+    fn review_lowerovrd(&self, self_old: &String, layer: &audk::Layer, against_codebase: bool, pairs: &mut Vec<Pair>) {
+        let lower_dst = if let Some(lower_dst) = layer.dst.as_ref() {
+            if !lower_dst.is_dir() {
+                println!("WRN: {}.dst is set but not a dir: {:?}", layer.name, &lower_dst);
                 return;
             }
+            lower_dst
         } else {
             return;
-        }
-        let find_renames = "100%";
-        let show_files   = vec![ibvovrd_dst];
+        };
+        let find_renames = self.find_renames.as_str();
+        let lower_dst_   = Some(lower_dst);
+        let show_files   = vec![&lower_dst_];
 
-        let v_path = std::path::PathBuf::from(audk::FWTO_WS).join(VIEW_HOME);
         let output = git::diff_no_format(&self_old, &self.new, true, find_renames, "A", &show_files, false);
         if !output.status.success() {
-            println!("review_ibvovrd.a: {:#?}", output);
+            println!("review_lowerovrd.a: {:#?}", output);
         } else {
             let diff_result = String::from_utf8(output.clone().stdout).unwrap();
             for line in diff_result.lines() {
-                let fibv = std::path::PathBuf::from(line);
-                let fsrc = std::path::PathBuf::from(fibv.strip_prefix(&ibvovrd_dst.unwrap().to_slash().unwrap()).unwrap());
-                // [1]: diff trees for better compare
-                let old = v_path.join(VIEW_OLD).join(&fsrc);
-                let new = v_path.join(VIEW_NEW).join(&fsrc);
-                // create old
-                git::create_file_from(&self.new, &fsrc, &old, None);
-                // create new
-                git::create_file_from(&self.new, &fibv, &new, None);
+                let flow = std::path::PathBuf::from(line);
+                let fsrc = std::path::PathBuf::from(flow.strip_prefix(&lower_dst.to_slash().unwrap()).unwrap());
+                // [1]: pair for better compare
+                pairs.push(Pair::new(&layer.name, &fsrc, (&self.new, &fsrc), (&self.new, &flow)));
             }
         }
 
         let output = git::diff_no_format(&self_old, &self.new, true, find_renames, "D", &show_files, false);
         if !output.status.success() {
-            println!("review_ibvovrd.d: {:#?}", output);
+            println!("review_lowerovrd.d: {:#?}", output);
         } else {
             let diff_result = String::from_utf8(output.clone().stdout).unwrap();
             for line in diff_result.lines() {
-                let fibv = std::path::PathBuf::from(line);
-                let fsrc = std::path::PathBuf::from(fibv.strip_prefix(&ibvovrd_dst.unwrap().to_slash().unwrap()).unwrap());
-                // [1]: diff trees for better compare
-                let old = v_path.join(VIEW_OLD).join(&fsrc);
-                let new = v_path.join(VIEW_NEW).join(&fsrc);
-                // create old
-                git::create_file_from(&self_old, &fibv, &old, None);
-                // create new
-                git::create_file_from(&self.new, &fsrc, &new, None);
+                let flow = std::path::PathBuf::from(line);
+                let fsrc = std::path::PathBuf::from(flow.strip_prefix(&lower_dst.to_slash().unwrap()).unwrap());
+                // [1]: pair for better compare
+                pairs.push(Pair::new(&layer.name, &fsrc, (&self_old, &flow), (&self.new, &fsrc)));
             }
         }
 
         let output = git::diff_no_format(&self_old, &self.new, true, find_renames, "M", &show_files, false);
         if !output.status.success() {
-            println!("review_ibvovrd.m: {:#?}", output);
+            println!("review_lowerovrd.m: {:#?}", output);
         } else {
             let diff_result = String::from_utf8(output.clone().stdout).unwrap();
             for line in diff_result.lines() {
-                let fibv = std::path::PathBuf::from(line);
-                let fsrc = std::path::PathBuf::from(fibv.strip_prefix(&ibvovrd_dst.unwrap().to_slash().unwrap()).unwrap());
-                // [1]: diff trees for better compare
-                let old = v_path.join(VIEW_OLD).join(&fsrc);
-                let new = v_path.join(VIEW_NEW).join(&fsrc);
-                // create old
-                git::create_file_from(&self_old, &fibv, &old, None);
-                // create new
-                git::create_file_from(&self.new, &fibv, &new, None);
+                let flow = std::path::PathBuf::from(line);
+                let fsrc = std::path::PathBuf::from(flow.strip_prefix(&lower_dst.to_slash().unwrap()).unwrap());
+                // [1]: pair for better compare, against codebase or previous override
+                if against_codebase {
+                    pairs.push(Pair::new(&layer.name, &fsrc, (&self.new, &fsrc), (&self.new, &flow)));
+                } else {
+                    pairs.push(Pair::new(&layer.name, &fsrc, (&self_old, &flow), (&self.new, &flow)));
+                }
+            }
+        }
+
+        for (fold, flow) in self.renames(self_old, &show_files, false) {
+            let fsrc = std::path::PathBuf::from(flow.strip_prefix(lower_dst.to_slash().unwrap()).unwrap());
+            // [1]: pair for better compare, against codebase or previous override of the old name
+            if against_codebase {
+                pairs.push(Pair::new(&layer.name, &fsrc, (&self.new, &fsrc), (&self.new, &flow)));
+            } else {
+                pairs.push(Pair::new(&layer.name, &fsrc, (self_old, &fold), (&self.new, &flow)));
             }
         }
     }
 
-    fn review_oemovrd(&self, self_old: &String, dst: &Option<&std::path::PathBuf>) {
-        let find_renames = "100%";
+    fn review_oemovrd(&self, self_old: &String, group: &str, dst: &Option<&std::path::PathBuf>, against_codebase: bool, pairs: &mut Vec<Pair>) {
+        let find_renames = self.find_renames.as_str();
         let show_files   = vec![dst];
 
-        let v_path = std::path::PathBuf::from(audk::FWTO_WS).join(VIEW_HOME);
         let output = git::diff_no_format(&self_old, &self.new, true, find_renames, "A", &show_files, false);
         if !output.status.success() {
             println!("review_oemovrd.a: {:#?}", output);
@@ -178,13 +531,8 @@ impl View {
             for line in diff_result.lines() {
                 let fdst = std::path::PathBuf::from(line);
                 let fsrc = std::path::PathBuf::from(fdst.strip_prefix(&dst.unwrap().to_slash().unwrap()).unwrap());
-                // [1]: diff trees for better compare
-                let old = v_path.join(VIEW_OLD).join(&fsrc);
-                let new = v_path.join(VIEW_NEW).join(&fsrc);
-                // create old
-                git::create_file_from(&self.new, &fsrc, &old, None);
-                // create new
-                git::create_file_from(&self.new, &fdst, &new, None);
+                // [1]: pair for better compare
+                pairs.push(Pair::new(group, &fsrc, (&self.new, &fsrc), (&self.new, &fdst)));
             }
         }
 
@@ -196,18 +544,9 @@ impl View {
             for line in diff_result.lines() {
                 let fdst = std::path::PathBuf::from(line);
                 let fsrc = std::path::PathBuf::from(fdst.strip_prefix(&dst.unwrap().to_slash().unwrap()).unwrap());
-                // [1]: diff trees for better compare
-                let old = v_path.join(VIEW_OLD).join(&fsrc);
-                let new = v_path.join(VIEW_NEW).join(&fsrc);
-                // create old
-                git::create_file_from(&self_old, &fdst, &old, None);
-                // create new
-                git::create_file_from(&self.new, &fsrc, &new, None);
-                if !new.is_file() {
-                    // diff may be   moved to another source
-                } else {
-                    // diff may be removed
-                }
+                // [1]: pair for better compare
+                pairs.push(Pair::new(group, &fsrc, (&self_old, &fdst), (&self.new, &fsrc)));
+                // diff may be moved to another source, or removed
             }
         }
 
@@ -219,13 +558,22 @@ impl View {
             for line in diff_result.lines() {
                 let fdst = std::path::PathBuf::from(line);
                 let fsrc = std::path::PathBuf::from(fdst.strip_prefix(&dst.unwrap().to_slash().unwrap()).unwrap());
-                // [1]: diff trees for better compare
-                let old = v_path.join(VIEW_OLD).join(&fsrc);
-                let new = v_path.join(VIEW_NEW).join(&fsrc);
-                // create old
-                git::create_file_from(&self_old, &fdst, &old, None);
-                // create new
-                git::create_file_from(&self.new, &fdst, &new, None);
+                // [1]: pair for better compare, against codebase or previous override
+                if against_codebase {
+                    pairs.push(Pair::new(group, &fsrc, (&self.new, &fsrc), (&self.new, &fdst)));
+                } else {
+                    pairs.push(Pair::new(group, &fsrc, (&self_old, &fdst), (&self.new, &fdst)));
+                }
+            }
+        }
+
+        for (fold, fdst) in self.renames(self_old, &show_files, false) {
+            let fsrc = std::path::PathBuf::from(fdst.strip_prefix(dst.unwrap().to_slash().unwrap()).unwrap());
+            // [1]: pair for better compare, against codebase or previous override of the old name
+            if against_codebase {
+                pairs.push(Pair::new(group, &fsrc, (&self.new, &fsrc), (&self.new, &fdst)));
+            } else {
+                pairs.push(Pair::new(group, &fsrc, (self_old, &fold), (&self.new, &fdst)));
             }
         }
     }
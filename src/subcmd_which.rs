@@ -0,0 +1,139 @@
+/*++ @file
+
+  Copyright ©2021 Liu Yi, liuyi28@lenovo.com
+
+  This program is just made available under the terms and conditions of the
+  MIT license: http://www.efikarl.com/mit-license.html
+
+  THE PROGRAM IS DISTRIBUTED UNDER THE MIT LICENSE ON AN "AS IS" BASIS,
+  WITHOUT WARRANTIES OR REPRESENTATIONS OF ANY KIND, EITHER EXPRESS OR IMPLIED.
+--*/
+
+use structopt::StructOpt;
+use path_slash::PathBufExt;
+
+use crate::audk;
+use crate::libs::cif;
+use crate::libs::ffs;
+use crate::libs::git;
+
+#[derive(StructOpt, Debug)]
+pub struct Which {
+    /// File relative to workspace, or the override of it
+    #[structopt(parse(from_os_str))]
+    pub path            : std::path::PathBuf,
+    /// Also list cif out of layers, by scanning the whole workspace
+    #[structopt(long)]
+    pub all             : bool,
+}
+
+/// One source of the file, which is in codebase or mapped by a cif
+struct Source {
+    layer               : String,
+    cif                 : Option<std::path::PathBuf>,
+    file                : std::path::PathBuf,
+    /// Order of build, and None if it is unknown
+    rank                : Option<usize>,
+}
+
+fn same_path(a: &std::path::PathBuf, b: &std::path::PathBuf) -> bool {
+    a.to_slash().unwrap().eq_ignore_ascii_case(&b.to_slash().unwrap())
+}
+
+impl Which {
+    pub fn handler(&self, layers: &audk::Layers) {
+        let fsrc = self.workspace_relative(layers);
+        println!("INF: which {:?}", fsrc);
+
+        let mut sources = vec![Source { layer: String::from("codebase"), cif: None, file: fsrc.clone(), rank: Some(0) }];
+        let mut layer_cifs = Vec::new();
+        for (i, layer) in layers.0.iter().enumerate() {
+            let mut mapped = false;
+            if let Some(cif) = layer.cif.as_ref() {
+                layer_cifs.push(cif.clone());
+                for entry in cif::entries(cif).iter().filter(|e| same_path(&e.src, &fsrc)) {
+                    mapped = true;
+                    sources.push(Source { layer: layer.name.clone(), cif: Some(cif.clone()), file: cif.parent().unwrap().join(&entry.dst), rank: Some(i + 1) });
+                }
+            }
+            // layer without cif, or its override is not mapped by cif
+            if let Some(dst) = layer.dst.as_ref() {
+                let fdst = dst.join(&fsrc);
+                if !mapped && fdst.is_file() {
+                    let rank = if layer.cif.is_none() { Some(i + 1) } else { None };
+                    sources.push(Source { layer: layer.name.clone(), cif: None, file: fdst, rank });
+                }
+            }
+        }
+        if self.all {
+            let mut others = Vec::new();
+            ffs::walk(".", &mut |path, is_dir| {
+                let name = path.file_name().unwrap().to_string_lossy();
+                if is_dir {
+                    return !name.starts_with('.') && name != "Build" && name != audk::FWTO_WS;
+                }
+                if path.extension().map(|e| e.eq_ignore_ascii_case("cif")).unwrap_or(false) {
+                    let cif = path.strip_prefix(".").unwrap().to_path_buf();
+                    if !layer_cifs.iter().any(|c| same_path(c, &cif)) {
+                        others.push(cif);
+                    }
+                }
+                false
+            });
+            for cif in others {
+                for entry in cif::entries(&cif).iter().filter(|e| same_path(&e.src, &fsrc)) {
+                    sources.push(Source { layer: String::from("-"), cif: Some(cif.clone()), file: cif.parent().unwrap().join(&entry.dst), rank: None });
+                }
+            }
+        }
+
+        // the highest existing source in order of build wins
+        let winner = sources.iter().enumerate().filter(|(_, c)| c.rank.is_some() && c.file.is_file()).max_by_key(|(_, c)| c.rank).map(|(i, _)| i);
+        let mut prev: Option<&Source> = None;
+        for (i, source) in sources.iter().enumerate() {
+            let mark = if Some(i) == winner { "*" } else { " " };
+            let cif  = match &source.cif {
+                Some(cif) => cif.to_slash().unwrap(),
+                None if i == 0 => String::from("-"),
+                None => String::from("(not mapped by cif)"),
+            };
+            let exists = if source.file.is_file() { "exists" } else { "missing" };
+            println!("  {} {:<10} {}", mark, source.layer, cif);
+            println!("      {} [{}]", source.file.to_slash().unwrap(), exists);
+            if source.rank.is_none() {
+                println!("      order of build is unknown");
+                continue;
+            }
+            if let Some(prev) = prev {
+                if let Some((add, del)) = git::diff_numstat_no_index(&prev.file, &source.file) {
+                    println!("      +{} -{} vs {}", add, del, prev.layer);
+                }
+            }
+            if source.file.is_file() {
+                prev = Some(source);
+            }
+        }
+        if winner.is_none() {
+            println!("WRN: no copy of {:?} exists", fsrc);
+        }
+    }
+
+    fn workspace_relative(&self, layers: &audk::Layers) -> std::path::PathBuf {
+        let ws   = std::env::current_dir().unwrap();
+        let path = std::path::PathBuf::from_slash(self.path.to_str().unwrap().replace('\\', "/"));
+        let path = if path.is_absolute() {
+            path.strip_prefix(&ws).map(|p| p.to_path_buf()).unwrap_or(path)
+        } else {
+            path
+        };
+        // map the override back to the file it overrides
+        for layer in &layers.0 {
+            if let Some(dst) = layer.dst.as_ref() {
+                if let Ok(fsrc) = path.strip_prefix(dst) {
+                    return fsrc.to_path_buf();
+                }
+            }
+        }
+        path
+    }
+}